@@ -7,3 +7,10 @@ pub mod motion;
 pub mod movement;
 /// Calculations and bounding of positions.
 pub mod position;
+/// Text objects.
+///
+/// A text object defines a region of text around the cursor, such as a word or a parenthesized
+/// block. Text objects can be used as argument for operators, like motions.
+pub mod text_object;
+/// Visual selections.
+pub mod visual;
@@ -1,8 +1,61 @@
 use caret::position::to_signed_pos;
-use edit::buffer::TextBuffer;
+use edit::buffer::{grapheme_count, TextBuffer};
 use io::parse::Inst;
 use state::editor::Editor;
 
+use std::cmp::min;
+
+#[derive(Copy, Clone, PartialEq)]
+/// The type of a motion, i.e. how the text between the start and the end of the motion is
+/// selected when it is used as argument for an operator.
+pub enum MotionType {
+    /// Characterwise, not including the character at the end.
+    Exclusive,
+    /// Characterwise, including the character at the end.
+    Inclusive,
+    /// Whole lines, from the line of the start to the line of the end.
+    Linewise,
+    /// A rectangular block, spanned by the start and the end.
+    Blockwise,
+}
+
+#[derive(Copy, Clone)]
+/// A region of text, as defined by a motion or a text object.
+pub struct Region {
+    /// The start of the region. This is always before (or, for blocks, above and left of) the
+    /// end.
+    pub start: (usize, usize),
+    /// The end of the region.
+    pub end: (usize, usize),
+    /// How the text in the region is selected.
+    pub kind: MotionType,
+}
+
+impl Region {
+    /// Create a new region between two positions, in any order.
+    pub fn new(a: (usize, usize), b: (usize, usize), kind: MotionType) -> Region {
+        let (start, end) = if kind == MotionType::Blockwise {
+            ((min(a.0, b.0), min(a.1, b.1)), (a.0.max(b.0), a.1.max(b.1)))
+        } else if (a.1, a.0) <= (b.1, b.0) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+
+        Region { start, end, kind }
+    }
+
+    /// Get the same region with another motion type.
+    pub fn with_kind(self, kind: MotionType) -> Region {
+        Region::new(self.start, self.end, kind)
+    }
+
+    /// Is the region empty, i.e. does it contain no text?
+    pub fn is_empty(&self) -> bool {
+        self.kind == MotionType::Exclusive && self.start == self.end
+    }
+}
+
 impl Editor {
     /// Convert an instruction to a motion (new coordinate). Returns None if the instructions given
     /// either is invalid or has no movement.
@@ -23,25 +76,35 @@ impl Editor {
             Char('k') => Some(self.up(n.d())),
             Char('g') => Some((0, n.or(1) - 1)),
             Char('G') => Some((0, self.buffers.current_buffer().len() - 1)),
-            Char('L') => Some((self.buffers.current_buffer()[y].len() - 1, y)),
-            Char('H') => Some((0, y)),
+            Char('J') => Some(self.down(15 * n.d())),
+            Char('K') => Some(self.up(15 * n.d())),
+            Char('L') | Char('$') => Some(self.bound((usize::MAX, y), true)),
+            Char('H') | Char('0') => Some((0, y)),
+            Char('w') => Some(self.next_word(n.d(), true)),
+            Char('e') => Some(self.next_word_end(n.d(), true)),
+            Char('%') => self.matching_bracket(),
+            Char('\'') => {
+                let ch = self.get_char();
+
+                self.mark_pos(ch).map(|(_, my)| {
+                    let my = self.bound_ver((0, my)).1;
+                    (self.buffers.current_buffer().get_indent(my).len(), my)
+                })
+            }
+            Char('`') => {
+                let ch = self.get_char();
+
+                self.mark_pos(ch).map(|p| self.bound(p, false))
+            }
             Char('t') => {
                 let ch = self.get_char();
 
-                if let Some(o) = self.next_ocur(ch, n.d()) {
-                    Some((o, y))
-                } else {
-                    None
-                }
+                self.next_ocur(ch, n.d()).map(|o| (o, y))
             }
             Char('f') => {
                 let ch = self.get_char();
 
-                if let Some(o) = self.previous_ocur(ch, n.d()) {
-                    Some((o, y))
-                } else {
-                    None
-                }
+                self.previous_ocur(ch, n.d()).map(|o| (o, y))
             }
             Char(c) => {
                 self.status_bar.msg = format!("Motion not defined: '{}'", c);
@@ -49,7 +112,7 @@ impl Editor {
                 None
             }
             _ => {
-                self.status_bar.msg = format!("Motion not defined");
+                self.status_bar.msg = "Motion not defined".to_string();
                 None
             }
         }
@@ -69,30 +132,93 @@ impl Editor {
             Char('k') => Some(self.up_unbounded(n.d())),
             Char('g') => Some((0, n.or(1) as isize - 1)),
             Char('G') => Some((
-                self.buffers.current_buffer()[y].len() as isize,
+                grapheme_count(&self.buffers.current_buffer()[y]) as isize,
                 self.buffers.current_buffer().len() as isize - 1,
             )),
-            Char('L') => Some(to_signed_pos((self.buffers.current_buffer()[y].len(), y))),
+            Char('L') => Some(to_signed_pos((
+                grapheme_count(&self.buffers.current_buffer()[y]),
+                y,
+            ))),
             Char('H') => Some((0, y as isize)),
+            Char('%') => self.matching_bracket().map(to_signed_pos),
+            Char('\'') => {
+                let ch = self.get_char();
+
+                self.mark_pos(ch).map(|(_, my)| (0, my as isize))
+            }
+            Char('`') => {
+                let ch = self.get_char();
+
+                self.mark_pos(ch).map(to_signed_pos)
+            }
             Char('t') => {
                 let ch = self.get_char();
 
-                if let Some(o) = self.next_ocur(ch, n.d()) {
-                    Some(to_signed_pos((o, y)))
-                } else {
-                    None
-                }
+                self.next_ocur(ch, n.d()).map(|o| to_signed_pos((o, y)))
             }
             Char('f') => {
                 let ch = self.get_char();
 
-                if let Some(o) = self.previous_ocur(ch, n.d()) {
-                    Some(to_signed_pos((o, y)))
-                } else {
-                    None
-                }
+                self.previous_ocur(ch, n.d()).map(|o| to_signed_pos((o, y)))
             }
             _ => None,
         }
     }
+
+    /// Convert an instruction to a region, going from the cursor to the position given by the
+    /// motion, or covering the text object. This is the argument of operators such as d.
+    ///
+    /// The motion type can be forced by preceding the motion with v (characterwise), V (linewise)
+    /// or [ctrl]v (blockwise).
+    pub fn to_region(&mut self, Inst(n, cmd): Inst) -> Option<Region> {
+        use self::MotionType::*;
+        use io::key::Key::*;
+
+        let pos = self.pos();
+        let (x, y) = pos;
+        let len = grapheme_count(&self.buffers.current_buffer()[y]);
+
+        match cmd.key {
+            Char('v') if cmd.ctrl => {
+                let inst = self.get_inst();
+                self.to_region(inst).map(|r| r.with_kind(Blockwise))
+            }
+            Char('v') => {
+                let inst = self.get_inst();
+                self.to_region(inst).map(|r| match r.kind {
+                    Exclusive => r.with_kind(Inclusive),
+                    _ => r.with_kind(Exclusive),
+                })
+            }
+            Char('V') => {
+                let inst = self.get_inst();
+                self.to_region(inst).map(|r| r.with_kind(Linewise))
+            }
+            Char(c @ 'i') | Char(c @ 'a') => {
+                let obj = self.get_char();
+                self.text_object(obj, c == 'a', n.d())
+            }
+            Char('h') | Left => Some(Region::new((x.saturating_sub(n.d()), y), pos, Exclusive)),
+            Char('l') | Right => Some(Region::new(pos, (min(x + n.d(), len), y), Exclusive)),
+            Char('0') | Char('H') => Some(Region::new((0, y), pos, Exclusive)),
+            Char('$') | Char('L') => Some(Region::new(pos, (len, y), Exclusive)),
+            Char('w') => {
+                // At the last word, the rest of the line is used
+                let next = self.next_word_forward(n.d());
+                let end = if next == 0 { len } else { x + next };
+                Some(Region::new(pos, (end, y), Exclusive))
+            }
+            Char('e') => Some(Region::new(pos, self.next_word_end(n.d(), true), Inclusive)),
+            Char('j') | Down | Char('k') | Up | Char('J') | Char('K') | Char('g') | Char('G')
+            | Char('\'') => self
+                .to_motion(Inst(n, cmd))
+                .map(|m| Region::new(pos, m, Linewise)),
+            Char('t') | Char('%') => self
+                .to_motion(Inst(n, cmd))
+                .map(|m| Region::new(pos, m, Inclusive)),
+            _ => self
+                .to_motion(Inst(n, cmd))
+                .map(|m| Region::new(pos, m, Exclusive)),
+        }
+    }
 }
@@ -1,12 +1,48 @@
-use edit::buffer::TextBuffer;
+use caret::position::char_at_col;
+use edit::buffer::{grapheme_chars, grapheme_count, grapheme_index, TextBuffer};
 use state::editor::Editor;
+use state::mode::{Mode, PrimitiveMode};
+use state::options::match_pair;
 
 impl Editor {
-    /// Goto a given position. Does not automatically bound.
+    /// Goto a given position. Does not automatically bound. The desired column is reset.
     #[inline]
     pub fn goto(&mut self, (x, y): (usize, usize)) {
         self.cursor_mut().y = y;
         self.cursor_mut().x = x;
+        self.cursor_mut().want_col = None;
+    }
+
+    /// Goto a position reached by a vertical motion, keeping the desired column.
+    pub fn goto_vertical(&mut self, pos: (usize, usize)) {
+        let want = self.want_col();
+        self.goto(pos);
+        self.cursor_mut().want_col = Some(want);
+    }
+
+    /// Get the display column vertical motions keep the cursor at, i.e. the desired column of the
+    /// cursor, or the column it is on.
+    pub fn want_col(&self) -> usize {
+        self.cursor()
+            .want_col
+            .unwrap_or_else(|| self.display_x(self.pos()))
+    }
+
+    /// Get the char shown at the desired column of the cursor on line `y`, or the end of the
+    /// line, if it is shorter.
+    pub fn at_want_col(&self, y: usize) -> usize {
+        let line = &self.buffers.current_buffer()[y];
+        let x = char_at_col(line, self.want_col(), self.int_option("tabstop"));
+        self.bound_hor((x, y), self.tight()).0
+    }
+
+    /// Can the cursor only be on the chars of a line, and not after its end? This is the case
+    /// outside of insert mode.
+    pub fn tight(&self) -> bool {
+        !matches!(
+            self.cursor().mode,
+            Mode::Primitive(PrimitiveMode::Insert(_))
+        )
     }
 
     /// Get the previous position, i.e. the position before the cursor (*not* left to the cursor).
@@ -25,21 +61,21 @@ impl Editor {
     #[inline]
     pub fn after(&self, n: usize, (x, y): (usize, usize)) -> Option<(usize, usize)> {
         // TODO: Make this more idiomatic {
-        if x + n < self.buffers.current_buffer()[y].len() {
+        if x + n < grapheme_count(&self.buffers.current_buffer()[y]) {
             Some((x + n, y))
         } else {
             if y + 1 >= self.buffers.current_buffer().len() {
                 None
             } else {
-                let mut mv = n + x - self.buffers.current_buffer()[y].len();
+                let mut mv = n + x - grapheme_count(&self.buffers.current_buffer()[y]);
                 let mut ry = y + 1;
 
                 loop {
-                    if mv < self.buffers.current_buffer()[ry].len() {
+                    if mv < grapheme_count(&self.buffers.current_buffer()[ry]) {
                         return Some((mv, ry));
                     } else {
                         if ry + 1 < self.buffers.current_buffer().len() {
-                            mv -= self.buffers.current_buffer()[ry].len();
+                            mv -= grapheme_count(&self.buffers.current_buffer()[ry]);
                             ry += 1;
                         } else {
                             return None;
@@ -64,11 +100,11 @@ impl Editor {
                 let mut ry = y - 1;
 
                 loop {
-                    if mv <= self.buffers.current_buffer()[ry].len() {
-                        return Some((self.buffers.current_buffer()[ry].len() - mv, ry));
+                    if mv <= grapheme_count(&self.buffers.current_buffer()[ry]) {
+                        return Some((grapheme_count(&self.buffers.current_buffer()[ry]) - mv, ry));
                     } else {
-                        if ry > 0 && mv >= self.buffers.current_buffer()[ry].len() {
-                            mv -= self.buffers.current_buffer()[ry].len();
+                        if ry > 0 && mv >= grapheme_count(&self.buffers.current_buffer()[ry]) {
+                            mv -= grapheme_count(&self.buffers.current_buffer()[ry]);
                             ry -= 1;
                         } else if ry == 0 {
                             return None;
@@ -116,14 +152,12 @@ impl Editor {
         (self.x() as isize - n as isize, self.y() as isize)
     }
 
-    /// Get the position of the character above the cursor (vertically bounded)
+    /// Get the position of the character above the cursor (vertically bounded), at the desired
+    /// column
     #[inline]
     pub fn up(&self, n: usize) -> (usize, usize) {
-        if n <= self.y() {
-            (self.cursor().x, self.y() - n)
-        } else {
-            (self.cursor().x, 0)
-        }
+        let y = self.y().saturating_sub(n);
+        (self.at_want_col(y), y)
     }
     /// Get the position of the character above the cursor (unbounded)
     #[inline]
@@ -131,10 +165,12 @@ impl Editor {
         (self.cursor().x as isize, self.y() as isize - n as isize)
     }
 
-    /// Get the position of the character under the cursor (vertically bounded)
+    /// Get the position of the character under the cursor (vertically bounded), at the desired
+    /// column
     #[inline]
     pub fn down(&self, n: usize) -> (usize, usize) {
-        self.bound_ver((self.cursor().x, self.y() + n))
+        let (_, y) = self.bound_ver((0, self.y() + n));
+        (self.at_want_col(y), y)
     }
     /// Get the position of the character above the cursor (unbounded)
     #[inline]
@@ -147,18 +183,15 @@ impl Editor {
         let mut dn = 0;
         let mut x = self.x();
 
-        for (i, ch) in self.buffers.current_buffer()[self.y()]
-            .chars()
+        for (i, ch) in grapheme_chars(&self.buffers.current_buffer()[self.y()])
             .skip(x)
             .enumerate()
         {
-            if ch == c {
-                if i > 0 {
-                    dn += 1;
-                    if dn == n {
-                        x += i;
-                        return Some(x);
-                    }
+            if ch == c && i > 0 {
+                dn += 1;
+                if dn == n {
+                    x += i;
+                    return Some(x);
                 }
             }
         }
@@ -172,10 +205,9 @@ impl Editor {
         let mut x = self.x();
         let y = self.y();
 
-        for (i, ch) in self.buffers.current_buffer()[y]
-            .chars()
+        for (i, ch) in grapheme_chars(&self.buffers.current_buffer()[y])
             .rev()
-            .skip(self.buffers.current_buffer()[y].len() - x)
+            .skip(grapheme_count(&self.buffers.current_buffer()[y]) - x)
             .enumerate()
         {
             if ch == c {
@@ -198,8 +230,7 @@ impl Editor {
         let x: usize = self.x();
         let mut has_ws = false;
 
-        for (i, current_char) in self.buffers.current_buffer()[self.y()]
-            .chars()
+        for (i, current_char) in grapheme_chars(&self.buffers.current_buffer()[self.y()])
             .skip(x)
             .enumerate()
         {
@@ -207,7 +238,7 @@ impl Editor {
                 has_ws = true;
             } else if has_ws && !current_char.is_whitespace() {
                 word_count += 1;
-                if word_count < n_opt - 1 {
+                if word_count < n_opt {
                     has_ws = false;
                 } else {
                     return i;
@@ -226,8 +257,7 @@ impl Editor {
         let mut word_char: bool = true;
         let mut last: usize = 0;
 
-        for (i, current_char) in self.buffers.current_buffer()[self.y()]
-            .chars()
+        for (i, current_char) in grapheme_chars(&self.buffers.current_buffer()[self.y()])
             .skip(x)
             .enumerate()
         {
@@ -245,4 +275,135 @@ impl Editor {
         }
         last
     }
+
+    /// Get the position of the bracket matching the one under the cursor. If the cursor is not on
+    /// a bracket, the first bracket after the cursor on the same line is used. The brackets are
+    /// given by the `matchpairs` option.
+    pub fn matching_bracket(&self) -> Option<(usize, usize)> {
+        let (x, y) = self.pos();
+        let buffer = self.buffers.current_buffer();
+        let pairs: Vec<(char, char)> = self
+            .list_option("matchpairs")
+            .iter()
+            .filter_map(|x| match_pair(x))
+            .collect();
+
+        let (start, open, close, forward) = grapheme_chars(&buffer[y])
+            .enumerate()
+            .skip(x)
+            .find_map(|(i, c)| {
+                pairs.iter().find_map(|&(open, close)| {
+                    if c == open {
+                        Some((i, open, close, true))
+                    } else if c == close {
+                        Some((i, open, close, false))
+                    } else {
+                        None
+                    }
+                })
+            })?;
+
+        let mut depth = 0usize;
+        if forward {
+            for (ry, line) in buffer.lines_from(y).enumerate() {
+                let ry = ry + y;
+                let skip = if ry == y { start } else { 0 };
+                for (rx, ch) in grapheme_chars(line).enumerate().skip(skip) {
+                    if ch == open {
+                        depth += 1;
+                    } else if ch == close {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some((rx, ry));
+                        }
+                    }
+                }
+            }
+        } else {
+            for ry in (0..y + 1).rev() {
+                let line: Vec<char> = grapheme_chars(&buffer[ry]).collect();
+                let end = if ry == y { start + 1 } else { line.len() };
+                for rx in (0..end).rev() {
+                    if line[rx] == close {
+                        depth += 1;
+                    } else if line[rx] == open {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some((rx, ry));
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Get the position of the n'th occurrence of `pattern` after (`forward` set) or before the
+    /// cursor. The search wraps around the end of the buffer.
+    pub fn find_pattern(&self, pattern: &str, n: usize, forward: bool) -> Option<(usize, usize)> {
+        if pattern.is_empty() {
+            return None;
+        }
+
+        let buffer = self.buffers.current_buffer();
+        let len = buffer.len();
+        let mut pos = self.pos();
+
+        for _ in 0..n {
+            let (x, y) = pos;
+            let mut found = None;
+
+            // Visit every line once, plus the cursor line again for matches on the other side of
+            // the cursor
+            for i in 0..len + 1 {
+                let ry = if forward {
+                    (y + i) % len
+                } else {
+                    (y + len * 2 - i) % len
+                };
+                let line = &buffer[ry];
+                let matches = line
+                    .match_indices(pattern)
+                    .map(|(b, _)| grapheme_index(line, b));
+
+                found = if forward {
+                    let mut matches = matches;
+                    matches.find(|&mx| (i > 0 || mx > x) && (i < len || mx <= x))
+                } else {
+                    matches
+                        .filter(|&mx| (i > 0 || mx < x) && (i < len || mx >= x))
+                        .last()
+                }
+                .map(|mx| (mx, ry));
+
+                if found.is_some() {
+                    break;
+                }
+            }
+
+            pos = found?;
+        }
+
+        Some(pos)
+    }
+
+    /// Go to the n'th next (`forward` set) or previous match of the last search pattern.
+    pub fn search_next(&mut self, n: usize, forward: bool) {
+        let pattern = match self.last_search.clone() {
+            Some(p) => p,
+            None => {
+                self.status_bar.msg = "No previous search pattern".to_owned();
+                return;
+            }
+        };
+
+        match self.find_pattern(&pattern, n, forward) {
+            Some(p) => {
+                self.push_jump();
+                self.goto(p);
+            }
+            None => self.status_bar.msg = format!("Pattern not found: {}", pattern),
+        }
+    }
 }
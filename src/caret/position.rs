@@ -1,12 +1,61 @@
-use edit::buffer::TextBuffer;
+use edit::buffer::{grapheme_count, TextBuffer};
 use state::editor::Editor;
 
+use std::cmp::max;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 /// Convert a usize tuple to isize
 pub fn to_signed_pos((x, y): (usize, usize)) -> (isize, isize) {
     (x as isize, y as isize)
 }
 
+/// Get the display width of a grapheme cluster starting at display column `col`. Tabs extend to
+/// the next multiple of `tabstop`, East Asian wide chars take two columns, and combining marks
+/// none. Every cluster takes at least a column, such that the cursor can be shown on it.
+pub fn grapheme_width(g: &str, col: usize, tabstop: usize) -> usize {
+    if g == "\t" {
+        tabstop - col % tabstop
+    } else {
+        max(g.width(), 1)
+    }
+}
+
+/// Get the display column of the grapheme cluster at index `x` of a line.
+pub fn display_col(line: &str, x: usize, tabstop: usize) -> usize {
+    line.graphemes(true)
+        .take(x)
+        .fold(0, |col, g| col + grapheme_width(g, col, tabstop))
+}
+
+/// Get the display width of a line.
+pub fn display_width(line: &str, tabstop: usize) -> usize {
+    display_col(line, usize::MAX, tabstop)
+}
+
+/// Get the index of the grapheme cluster of a line shown at a display column, or the number of
+/// clusters if the column is after the end of the line.
+pub fn char_at_col(line: &str, col: usize, tabstop: usize) -> usize {
+    let mut end = 0;
+    for (x, g) in line.graphemes(true).enumerate() {
+        end += grapheme_width(g, end, tabstop);
+        if col < end {
+            return x;
+        }
+    }
+    grapheme_count(line)
+}
+
 impl Editor {
+    /// Get the display column of a position in the current buffer, i.e. the column with tabs
+    /// expanded to `tabstop`.
+    pub fn display_x(&self, (x, y): (usize, usize)) -> usize {
+        match self.buffers.current_buffer().get_line(y) {
+            Some(line) => display_col(line, x, self.int_option("tabstop")),
+            None => x,
+        }
+    }
+
     /// Get the position of the current cursor, bounded
     #[inline]
     pub fn pos(&self) -> (usize, usize) {
@@ -35,7 +84,7 @@ impl Editor {
             y
         };
 
-        let ln = self.buffers.current_buffer()[y].len() + if tight { 0 } else { 1 };
+        let ln = grapheme_count(&self.buffers.current_buffer()[y]) + if tight { 0 } else { 1 };
         if x >= ln {
             if ln == 0 {
                 (0, y)
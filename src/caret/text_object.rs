@@ -0,0 +1,230 @@
+use caret::motion::{MotionType, Region};
+use edit::buffer::{grapheme_chars, grapheme_count, TextBuffer};
+use state::editor::Editor;
+
+/// The class of a character, used for finding word boundaries.
+#[derive(Copy, Clone, PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(c: char, big_word: bool) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if big_word || c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+impl Editor {
+    /// Get the region of a text object. `around` selects the "a" variant of the object (including
+    /// surrounding whitespace or delimiters), otherwise the "inner" variant is used.
+    ///
+    /// The following objects are defined:
+    ///
+    /// - `w`/`W`: word/WORD
+    /// - `(`, `)`, `b`: parenthesized block
+    /// - `{`, `}`, `B`: braced block
+    /// - `[`, `]`: bracketed block
+    /// - `<`, `>`: angle bracketed block
+    /// - `"`, `'`, `` ` ``: quoted string
+    pub fn text_object(&mut self, c: char, around: bool, n: usize) -> Option<Region> {
+        match c {
+            'w' => self.word_object(around, n, false),
+            'W' => self.word_object(around, n, true),
+            '(' | ')' | 'b' => self.block_object('(', ')', around, n),
+            '{' | '}' | 'B' => self.block_object('{', '}', around, n),
+            '[' | ']' => self.block_object('[', ']', around, n),
+            '<' | '>' => self.block_object('<', '>', around, n),
+            '"' | '\'' | '`' => self.quote_object(c, around),
+            _ => {
+                self.status_bar.msg = format!("Text object not defined: '{}'", c);
+                None
+            }
+        }
+    }
+
+    /// The word (or WORD) under the cursor, and the n - 1 following.
+    fn word_object(&self, around: bool, n: usize, big_word: bool) -> Option<Region> {
+        let (x, y) = self.pos();
+        let line: Vec<char> = grapheme_chars(&self.buffers.current_buffer()[y]).collect();
+        if line.is_empty() {
+            return None;
+        }
+
+        let class = |i: usize| char_class(line[i], big_word);
+
+        let mut start = x;
+        while start > 0 && class(start - 1) == class(x) {
+            start -= 1;
+        }
+
+        let mut end = x;
+        for i in 0..n {
+            if i > 0 {
+                if end + 1 >= line.len() {
+                    break;
+                }
+                end += 1;
+            }
+            while end + 1 < line.len() && class(end + 1) == class(end) {
+                end += 1;
+            }
+        }
+
+        if around {
+            if end + 1 < line.len() && class(end + 1) == CharClass::Whitespace {
+                // Include the trailing whitespace
+                end += 1;
+                while end + 1 < line.len() && class(end + 1) == CharClass::Whitespace {
+                    end += 1;
+                }
+            } else {
+                // ...or the leading whitespace, if there is no trailing
+                while start > 0 && class(start - 1) == CharClass::Whitespace {
+                    start -= 1;
+                }
+            }
+        }
+
+        Some(Region::new((start, y), (end, y), MotionType::Inclusive))
+    }
+
+    /// The n'th block delimited by `open` and `close` surrounding the cursor.
+    fn block_object(&self, open: char, close: char, around: bool, n: usize) -> Option<Region> {
+        let buffer = self.buffers.current_buffer();
+        let (x, y) = self.pos();
+
+        // Search backwards for the unmatched opening delimiter
+        let mut depth = 0;
+        let mut levels = n;
+        let mut start = None;
+        'search: for ry in (0..y + 1).rev() {
+            let line: Vec<char> = grapheme_chars(&buffer[ry]).collect();
+            let end = if ry == y {
+                (x + 1).min(line.len())
+            } else {
+                line.len()
+            };
+            for rx in (0..end).rev() {
+                if line[rx] == close && (rx, ry) != (x, y) {
+                    depth += 1;
+                } else if line[rx] == open {
+                    if depth == 0 {
+                        levels -= 1;
+                        if levels == 0 {
+                            start = Some((rx, ry));
+                            break 'search;
+                        }
+                    } else {
+                        depth -= 1;
+                    }
+                }
+            }
+        }
+        let (ox, oy) = start?;
+
+        // Search forwards for the matching closing delimiter
+        let mut depth = 0;
+        let mut end = None;
+        'matching: for (ry, line) in buffer.lines_from(oy).enumerate() {
+            let ry = ry + oy;
+            let skip = if ry == oy { ox + 1 } else { 0 };
+            for (rx, c) in grapheme_chars(line).enumerate().skip(skip) {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        end = Some((rx, ry));
+                        break 'matching;
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+        let (cx, cy) = end?;
+
+        if around {
+            Some(Region::new((ox, oy), (cx, cy), MotionType::Inclusive))
+        } else if ox + 1 >= grapheme_count(&buffer[oy]) && oy < cy {
+            // The opening delimiter ends the line, so the inner block starts on the next line
+            Some(Region::new((0, oy + 1), (cx, cy), MotionType::Exclusive))
+        } else {
+            Some(Region::new((ox + 1, oy), (cx, cy), MotionType::Exclusive))
+        }
+    }
+
+    /// The string quoted with `quote` under (or after) the cursor.
+    fn quote_object(&self, quote: char, around: bool) -> Option<Region> {
+        let (x, y) = self.pos();
+        let line: Vec<char> = grapheme_chars(&self.buffers.current_buffer()[y]).collect();
+
+        let quotes: Vec<usize> = (0..line.len())
+            .filter(|&i| line[i] == quote && (i == 0 || line[i - 1] != '\\'))
+            .collect();
+
+        let (start, end) = quotes
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|&(_, end)| end >= x)?;
+
+        if around {
+            let mut end = end;
+            while end + 1 < line.len() && line[end + 1].is_whitespace() {
+                end += 1;
+            }
+            Some(Region::new((start, y), (end, y), MotionType::Inclusive))
+        } else {
+            Some(Region::new((start + 1, y), (end, y), MotionType::Exclusive))
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "orbital")))]
+mod tests {
+    use super::*;
+    use edit::buffer::SplitBuffer;
+
+    /// Get the text of an object in `text`, with the cursor at `pos`.
+    fn object(text: &str, pos: (usize, usize), c: char, around: bool, n: usize) -> Vec<String> {
+        let mut editor = Editor::new();
+        *editor.buffers.current_buffer_mut() = SplitBuffer::from_str(text);
+        editor.goto(pos);
+        editor
+            .text_object(c, around, n)
+            .map_or(Vec::new(), |region| editor.region_text(&region))
+    }
+
+    #[test]
+    fn words_and_their_whitespace() {
+        assert_eq!(object("one two three", (5, 0), 'w', false, 1), ["two"]);
+        assert_eq!(object("one two three", (5, 0), 'w', true, 1), ["two "]);
+        assert_eq!(object("one two", (5, 0), 'w', true, 1), [" two"]);
+        assert_eq!(object("one two three", (0, 0), 'w', false, 3), ["one two"]);
+        assert_eq!(object("a.b c", (0, 0), 'W', false, 1), ["a.b"]);
+    }
+
+    #[test]
+    fn nested_blocks() {
+        assert_eq!(object("f(a, (b))", (2, 0), '(', false, 1), ["a, (b)"]);
+        assert_eq!(object("f(a, (b))", (6, 0), 'b', false, 1), ["b"]);
+        assert_eq!(object("f(a, (b))", (6, 0), ')', true, 2), ["(a, (b))"]);
+        assert_eq!(object("{\n    a\n}", (4, 1), 'B', false, 1), ["    a", ""]);
+        assert!(object("a[b]", (0, 0), '[', false, 1).is_empty());
+    }
+
+    #[test]
+    fn quoted_strings() {
+        assert_eq!(
+            object(r#"a "b \" c" d"#, (0, 0), '"', false, 1),
+            [r#"b \" c"#]
+        );
+        assert_eq!(object("'a' 'b' c", (5, 0), '\'', true, 1), ["'b' "]);
+        assert!(object("a 'b", (0, 0), '\'', false, 1).is_empty());
+    }
+}
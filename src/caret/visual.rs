@@ -0,0 +1,71 @@
+use caret::motion::{MotionType, Region};
+use edit::buffer::TextBuffer;
+use edit::operator::Operator;
+use io::redraw::RedrawTask;
+use state::editor::Editor;
+use state::mode::{CommandMode, Mode, VisualMode};
+
+impl Editor {
+    /// Start a visual selection of the given kind at the cursor, or switch the kind of the current
+    /// selection. Starting the kind which is already active leaves visual mode.
+    pub fn start_visual(&mut self, kind: VisualMode) {
+        match self.cursor().mode {
+            Mode::Command(CommandMode::Visual(current)) if current == kind => {
+                self.cursor_mut().mode = Mode::Command(CommandMode::Normal);
+            }
+            Mode::Command(CommandMode::Visual(_)) => {
+                self.cursor_mut().mode = Mode::Command(CommandMode::Visual(kind));
+            }
+            _ => {
+                let pos = self.pos();
+                let cursor = self.cursor_mut();
+                cursor.anchor = pos;
+                cursor.mode = Mode::Command(CommandMode::Visual(kind));
+            }
+        }
+        self.redraw_task = RedrawTask::Full;
+    }
+
+    /// Get the region of the visual selection, if in visual mode.
+    pub fn visual_region(&self) -> Option<Region> {
+        let kind = match self.cursor().mode {
+            Mode::Command(CommandMode::Visual(VisualMode::Char)) => MotionType::Inclusive,
+            Mode::Command(CommandMode::Visual(VisualMode::Line)) => MotionType::Linewise,
+            _ => return None,
+        };
+        let anchor = self.bound(self.cursor().anchor, true);
+
+        Some(Region::new(anchor, self.pos(), kind))
+    }
+
+    /// Swap the cursor and the anchor of the visual selection.
+    pub fn swap_visual_ends(&mut self) {
+        let pos = self.pos();
+        let anchor = self.cursor().anchor;
+        self.cursor_mut().anchor = pos;
+        self.goto(anchor);
+    }
+
+    /// Leave visual mode, and apply an operator on the selection. Shifts are done `n` times.
+    pub fn visual_operator(&mut self, op: Operator, n: usize) {
+        let region = match self.visual_region() {
+            Some(region) => region,
+            None => return,
+        };
+        self.cursor_mut().mode = Mode::Command(CommandMode::Normal);
+
+        match op {
+            Operator::ShiftRight | Operator::ShiftLeft => {
+                let (sy, ey) = (region.start.1, region.end.1);
+                self.shift_lines(sy, ey, op == Operator::ShiftRight, n);
+                self.push_change();
+                let indent = self.buffers.current_buffer().get_indent(sy).len();
+                self.goto((indent, sy));
+            }
+            _ => self.apply_operator(op, region),
+        }
+
+        // Clear the highlighting of the selection
+        self.redraw_task = RedrawTask::Full;
+    }
+}
@@ -0,0 +1,926 @@
+use core::registry::Argument::{Char, Motion};
+use core::registry::Scope::{Global, Insert, Normal, Prompt, Visual};
+use core::registry::{Argument, Registry};
+use edit::buffer::{byte_index, grapheme_count, TextBuffer};
+use edit::insert::{InsertMode, InsertOptions};
+use edit::operator::Operator;
+use io::layout::ScrollPlace;
+use io::parse::Parameter;
+use io::redraw::RedrawTask;
+use state::editor::Editor;
+use state::mode::{CommandMode, Mode, PrimitiveMode, VisualMode};
+
+use std::cmp::{max, min};
+
+/// No argument (the `Argument::None` variant, which can't be imported next to `Option::None`).
+const NO_ARG: Argument = Argument::None;
+
+/// Go to insert mode with the given insert mode type.
+fn insert_mode(e: &mut Editor, mode: InsertMode) {
+    e.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions { mode }));
+}
+
+/// Go to normal mode, moving the cursor one left (as when leaving insert mode).
+fn leave_insert(e: &mut Editor, _: Parameter) {
+    let left = e.left(1);
+    e.goto(left);
+    e.cursor_mut().mode = Mode::Command(CommandMode::Normal);
+}
+
+fn go_left(e: &mut Editor, n: Parameter) {
+    let left = e.left(n.d());
+    e.goto(left);
+}
+
+fn go_down(e: &mut Editor, n: Parameter) {
+    let down = if e.bool_option("displaylines") {
+        e.display_down(n.d(), false)
+    } else {
+        e.down(n.d())
+    };
+    e.goto_vertical(down);
+}
+
+fn go_up(e: &mut Editor, n: Parameter) {
+    let up = if e.bool_option("displaylines") {
+        e.display_down(n.d(), true)
+    } else {
+        e.up(n.d())
+    };
+    e.goto_vertical(up);
+}
+
+fn go_display_down(e: &mut Editor, n: Parameter) {
+    let down = e.display_down(n.d(), false);
+    e.goto_vertical(down);
+}
+
+fn go_display_up(e: &mut Editor, n: Parameter) {
+    let up = e.display_down(n.d(), true);
+    e.goto_vertical(up);
+}
+
+fn go_display_start(e: &mut Editor, _: Parameter) {
+    let start = e.display_line_end(false);
+    e.goto(start);
+}
+
+fn go_display_end(e: &mut Editor, _: Parameter) {
+    let end = e.display_line_end(true);
+    e.goto(end);
+}
+
+fn go_right(e: &mut Editor, n: Parameter) {
+    let right = e.right(n.d(), true);
+    e.goto(right);
+}
+
+/// Go to the end of the line, and keep to the end of lines in vertical motions.
+fn go_line_end(e: &mut Editor, _: Parameter) {
+    let y = e.y();
+    let len = grapheme_count(&e.buffers.current_buffer()[y]);
+    e.goto((len.saturating_sub(1), y));
+    e.cursor_mut().want_col = Some(usize::MAX);
+}
+
+fn go_line_start(e: &mut Editor, _: Parameter) {
+    let y = e.y();
+    e.goto((0, y));
+}
+
+/// The lines scrolled by [ctrl]d and [ctrl]u: <numeral>, or half the text area.
+fn half_page(e: &Editor, n: Parameter) -> usize {
+    match n {
+        Parameter::Int(n) => n,
+        _ => max(e.text_area().1 / 2, 1),
+    }
+}
+
+fn jump_newer(e: &mut Editor, n: Parameter) {
+    e.jump_newer(n.d());
+}
+
+/// Register the built-in commands.
+pub fn default_registry() -> Registry {
+    let mut r = Registry::new();
+
+    // Global commands
+    r.register(
+        Global,
+        "<S-Space>",
+        false,
+        NO_ARG,
+        "Go back to normal mode",
+        |e, _| e.cursor_mut().mode = Mode::Command(CommandMode::Normal),
+    );
+    r.register(
+        Global,
+        "<A-Space>",
+        false,
+        NO_ARG,
+        "Go to the next cursor",
+        |e, _| e.next_cursor(),
+    );
+
+    // Mode switching
+    r.register(Normal, "i", false, NO_ARG, "Go to insert mode", |e, _| {
+        insert_mode(e, InsertMode::Insert)
+    });
+    r.register(
+        Normal,
+        "I",
+        false,
+        NO_ARG,
+        "Go to insert mode at the start of the line",
+        |e, _| {
+            let y = e.y();
+            e.goto((0, y));
+            insert_mode(e, InsertMode::Insert);
+        },
+    );
+    r.register(
+        Normal,
+        "a",
+        false,
+        NO_ARG,
+        "Go to insert (append) mode",
+        |e, _| {
+            let pos = e.right(1, false);
+            e.goto(pos);
+            insert_mode(e, InsertMode::Insert);
+        },
+    );
+    r.register(
+        Normal,
+        "A",
+        false,
+        NO_ARG,
+        "Go to insert mode at the end of the line",
+        |e, _| {
+            let pos = (grapheme_count(&e.buffers.current_buffer()[e.y()]), e.y());
+            e.goto(pos);
+            insert_mode(e, InsertMode::Insert);
+        },
+    );
+    r.register(Normal, "o", false, NO_ARG, "Insert a new line", |e, _| {
+        let y = e.y();
+        let ind = e.auto_indent(y);
+        let last = ind.len();
+        e.insert_line(y + 1, ind);
+        e.goto((last, y + 1));
+        insert_mode(e, InsertMode::Insert);
+    });
+    r.register(
+        Normal,
+        "O",
+        false,
+        NO_ARG,
+        "Insert a new line above",
+        |e, _| {
+            let y = e.y();
+            let ind = e.auto_indent_above(y);
+            let last = ind.len();
+            e.insert_line(y, ind);
+            e.goto((last, y));
+            insert_mode(e, InsertMode::Insert);
+        },
+    );
+    r.register(Normal, "R", false, NO_ARG, "Go to replace mode", |e, _| {
+        insert_mode(e, InsertMode::Replace)
+    });
+    r.register(Normal, ";", false, NO_ARG, "Go to prompt mode", |e, _| {
+        e.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Prompt)
+    });
+
+    // Basic motion
+    r.register(Normal, "h", true, NO_ARG, "Go left", go_left);
+    r.register(Normal, "<Left>", true, NO_ARG, "Go left", go_left);
+    r.register(Normal, "j", true, NO_ARG, "Go down", go_down);
+    r.register(Normal, "<Down>", true, NO_ARG, "Go down", go_down);
+    r.register(Normal, "k", true, NO_ARG, "Go up", go_up);
+    r.register(Normal, "<Up>", true, NO_ARG, "Go up", go_up);
+    r.register(Normal, "l", true, NO_ARG, "Go right", go_right);
+    r.register(Normal, "<Right>", true, NO_ARG, "Go right", go_right);
+    r.register(Normal, "w", true, NO_ARG, "Go to the next word", |e, n| {
+        let next_word = e.next_word(n.d(), true);
+        e.goto(next_word);
+    });
+    r.register(
+        Normal,
+        "e",
+        true,
+        NO_ARG,
+        "Go to the end of the word",
+        |e, n| {
+            let next_word = e.next_word_end(n.d(), true);
+            e.goto(next_word);
+        },
+    );
+    r.register(Normal, "J", true, NO_ARG, "Go 15 down", |e, n| {
+        let down = e.down(15 * n.d());
+        e.goto_vertical(down);
+    });
+    r.register(Normal, "K", true, NO_ARG, "Go 15 up", |e, n| {
+        let up = e.up(15 * n.d());
+        e.goto_vertical(up);
+    });
+    r.register(
+        Normal,
+        "L",
+        false,
+        NO_ARG,
+        "Go to the end of the line",
+        go_line_end,
+    );
+    r.register(
+        Normal,
+        "$",
+        false,
+        NO_ARG,
+        "Go to the end of the line",
+        go_line_end,
+    );
+    r.register(
+        Normal,
+        "H",
+        false,
+        NO_ARG,
+        "Go to the start of the line",
+        go_line_start,
+    );
+    r.register(
+        Normal,
+        "0",
+        false,
+        NO_ARG,
+        "Go to the start of the line",
+        go_line_start,
+    );
+
+    for &scope in &[Normal, Visual] {
+        r.register(
+            scope,
+            "gj",
+            true,
+            NO_ARG,
+            "Go down a screen row",
+            go_display_down,
+        );
+        r.register(
+            scope,
+            "gk",
+            true,
+            NO_ARG,
+            "Go up a screen row",
+            go_display_up,
+        );
+        r.register(
+            scope,
+            "g0",
+            false,
+            NO_ARG,
+            "Go to the start of the screen row",
+            go_display_start,
+        );
+        r.register(
+            scope,
+            "g$",
+            false,
+            NO_ARG,
+            "Go to the end of the screen row",
+            go_display_end,
+        );
+    }
+    // In visual mode, other motions are handled by `to_motion`
+    r.register(Visual, "j", true, NO_ARG, "Go down", go_down);
+    r.register(Visual, "<Down>", true, NO_ARG, "Go down", go_down);
+    r.register(Visual, "k", true, NO_ARG, "Go up", go_up);
+    r.register(Visual, "<Up>", true, NO_ARG, "Go up", go_up);
+    r.register(
+        Visual,
+        "$",
+        false,
+        NO_ARG,
+        "Go to the end of the line",
+        go_line_end,
+    );
+
+    // Navigation
+    r.register(
+        Normal,
+        "g",
+        true,
+        Motion,
+        "Go to <numeral> line, or do <motion>",
+        |e, n| {
+            if let Parameter::Int(n) = n {
+                e.push_jump();
+                e.goto((0, n.wrapping_sub(1)));
+            } else {
+                let inst = e.get_inst();
+                if let Some(m) = e.to_motion(inst) {
+                    e.push_jump();
+                    e.goto(m); // fix
+                }
+            }
+        },
+    );
+    r.register(
+        Normal,
+        "G",
+        false,
+        NO_ARG,
+        "Go to the end of the document",
+        |e, _| {
+            let last = e.buffers.current_buffer().len() - 1;
+            e.push_jump();
+            e.goto((0, last));
+        },
+    );
+    r.register(
+        Normal,
+        "t",
+        true,
+        Char,
+        "Go to the next occurence of <char>",
+        |e, n| {
+            let ch = e.get_char();
+            if let Some(p) = e.next_ocur(ch, n.d()) {
+                let y = e.y();
+                e.goto((p, y));
+            }
+        },
+    );
+    r.register(
+        Normal,
+        "f",
+        true,
+        Char,
+        "Go to the previous occurence of <char>",
+        |e, n| {
+            let ch = e.get_char();
+            if let Some(p) = e.previous_ocur(ch, n.d()) {
+                let y = e.y();
+                e.goto((p, y));
+            }
+        },
+    );
+    r.register(
+        Normal,
+        "%",
+        false,
+        NO_ARG,
+        "Go to the matching bracket",
+        |e, _| {
+            if let Some(m) = e.matching_bracket() {
+                e.push_jump();
+                e.goto(m);
+            }
+        },
+    );
+    r.register(
+        Normal,
+        "n",
+        true,
+        NO_ARG,
+        "Go to the next match of the last search",
+        |e, n| e.search_next(n.d(), true),
+    );
+    r.register(
+        Normal,
+        "N",
+        true,
+        NO_ARG,
+        "Go to the previous match of the last search",
+        |e, n| e.search_next(n.d(), false),
+    );
+
+    // Marks and jumps
+    r.register(Normal, "m", false, Char, "Set the mark <char>", |e, _| {
+        let c = e.get_char();
+        e.set_mark(c);
+        e.redraw_task = RedrawTask::StatusBar;
+    });
+    r.register(
+        Normal,
+        "'",
+        false,
+        Char,
+        "Go to the line of the mark <char>",
+        |e, _| {
+            let c = e.get_char();
+            e.goto_mark(c, true);
+        },
+    );
+    r.register(
+        Normal,
+        "`",
+        false,
+        Char,
+        "Go to the position of the mark <char>",
+        |e, _| {
+            let c = e.get_char();
+            e.goto_mark(c, false);
+        },
+    );
+    r.register(
+        Normal,
+        "<C-o>",
+        true,
+        NO_ARG,
+        "Go to an older position in the jump list",
+        |e, n| e.jump_older(n.d()),
+    );
+    r.register(
+        Normal,
+        "<C-i>",
+        true,
+        NO_ARG,
+        "Go to a newer position in the jump list",
+        jump_newer,
+    );
+    r.register(
+        Normal,
+        "<Tab>",
+        true,
+        NO_ARG,
+        "Go to a newer position in the jump list",
+        jump_newer,
+    );
+    r.register(
+        Normal,
+        "g;",
+        true,
+        NO_ARG,
+        "Go to an older position in the change list",
+        |e, n| e.goto_change(n.d(), true),
+    );
+    r.register(
+        Normal,
+        "g,",
+        true,
+        NO_ARG,
+        "Go to a newer position in the change list",
+        |e, n| e.goto_change(n.d(), false),
+    );
+
+    // Scrolling
+    r.register(
+        Normal,
+        "z",
+        true,
+        Motion,
+        "Scroll to line <numeral>, or scroll to <motion>",
+        |e, n| {
+            if let Parameter::Int(n) = n {
+                let last = e.buffers.current_buffer().len() - 1;
+                e.buffers.current_buffer_info_mut().scroll_y = min(n.saturating_sub(1), last);
+                e.cursor_to_screen();
+                e.redraw_task = RedrawTask::Full;
+            } else {
+                let inst = e.get_inst();
+                if let Some(m) = e.to_motion(inst) {
+                    e.goto(m);
+                    e.scroll_cursor_to(ScrollPlace::Top);
+                }
+            }
+        },
+    );
+    r.register(
+        Normal,
+        "zt",
+        false,
+        NO_ARG,
+        "Scroll the line of the cursor to the top",
+        |e, _| e.scroll_cursor_to(ScrollPlace::Top),
+    );
+    r.register(
+        Normal,
+        "zz",
+        false,
+        NO_ARG,
+        "Scroll the line of the cursor to the middle",
+        |e, _| e.scroll_cursor_to(ScrollPlace::Middle),
+    );
+    r.register(
+        Normal,
+        "zb",
+        false,
+        NO_ARG,
+        "Scroll the line of the cursor to the bottom",
+        |e, _| e.scroll_cursor_to(ScrollPlace::Bottom),
+    );
+    r.register(
+        Normal,
+        "Z",
+        false,
+        NO_ARG,
+        "Scroll to the cursor",
+        |e, _| {
+            e.buffers.current_buffer_info_mut().scroll_y = e.y().saturating_sub(3);
+            e.redraw_task = RedrawTask::Full;
+        },
+    );
+    r.register(
+        Normal,
+        "<C-e>",
+        true,
+        NO_ARG,
+        "Scroll a line down",
+        |e, n| e.scroll_by(n.d(), false),
+    );
+    r.register(Normal, "<C-y>", true, NO_ARG, "Scroll a line up", |e, n| {
+        e.scroll_by(n.d(), true)
+    });
+    r.register(
+        Normal,
+        "<C-d>",
+        true,
+        NO_ARG,
+        "Scroll half a page (or <numeral> lines) down",
+        |e, n| {
+            let lines = half_page(e, n);
+            e.scroll_with_cursor(lines, false)
+        },
+    );
+    r.register(
+        Normal,
+        "<C-u>",
+        true,
+        NO_ARG,
+        "Scroll half a page (or <numeral> lines) up",
+        |e, n| {
+            let lines = half_page(e, n);
+            e.scroll_with_cursor(lines, true)
+        },
+    );
+    r.register(
+        Normal,
+        "<C-f>",
+        true,
+        NO_ARG,
+        "Scroll a page down",
+        |e, n| e.scroll_page(n.d(), false),
+    );
+    r.register(Normal, "<C-b>", true, NO_ARG, "Scroll a page up", |e, n| {
+        e.scroll_page(n.d(), true)
+    });
+
+    // Cursor management
+    r.register(Normal, "b", false, NO_ARG, "Branch the cursor", |e, _| {
+        if e.buffers.current_buffer_info().cursors.len() < 255 {
+            let cursor = e.cursor().clone();
+            let current_cursor_index = e.buffers.current_buffer_info().current_cursor as usize;
+            e.buffers
+                .current_buffer_info_mut()
+                .cursors
+                .insert(current_cursor_index, cursor);
+            e.next_cursor();
+        } else {
+            e.status_bar.msg = "At max 255 cursors".to_owned();
+        }
+    });
+    r.register(
+        Normal,
+        "B",
+        false,
+        NO_ARG,
+        "Delete the current cursor",
+        |e, _| {
+            if e.buffers.current_buffer_info().cursors.len() > 1 {
+                let current_cursor_index = e.buffers.current_buffer_info().current_cursor;
+                e.buffers
+                    .current_buffer_info_mut()
+                    .cursors
+                    .remove(current_cursor_index as usize);
+                e.prev_cursor();
+            } else {
+                e.status_bar.msg = "No other cursors!".to_owned();
+            }
+        },
+    );
+    r.register(
+        Normal,
+        "<Space>",
+        false,
+        NO_ARG,
+        "Go to the next cursor",
+        |e, _| e.next_cursor(),
+    );
+
+    // Editing
+    r.register(Normal, "x", false, NO_ARG, "Delete char", |e, _| {
+        e.delete();
+        let bounded = e.bound(e.pos(), true);
+        e.goto(bounded);
+    });
+    r.register(Normal, "X", false, NO_ARG, "Backspace char", |e, _| {
+        e.backspace();
+        let bounded = e.bound(e.pos(), true);
+        e.goto(bounded);
+    });
+    r.register(
+        Normal,
+        "r",
+        false,
+        Char,
+        "Replace the current char with <char>",
+        |e, _| {
+            let (x, y) = e.pos();
+            let c = e.get_char();
+            e.push_change();
+            let current_buffer = e.buffers.current_buffer_info_mut();
+            let line = &current_buffer.raw_buffer[y];
+            let range = byte_index(line, x)..byte_index(line, x + 1);
+            current_buffer.raw_buffer[y].replace_range(range, &c.to_string());
+        },
+    );
+    r.register(
+        Normal,
+        "~",
+        true,
+        NO_ARG,
+        "Switch the character under the cursor with its counterpart",
+        |e, n| e.invert_chars(n.d()),
+    );
+    r.register(
+        Normal,
+        "p",
+        true,
+        NO_ARG,
+        "Put the register after the cursor",
+        |e, n| e.put(true, n.d()),
+    );
+    r.register(
+        Normal,
+        "P",
+        true,
+        NO_ARG,
+        "Put the register before the cursor",
+        |e, n| e.put(false, n.d()),
+    );
+    r.register(
+        Normal,
+        ".",
+        false,
+        NO_ARG,
+        "Repeat the previous command",
+        |e, _| {
+            if let Some(inst) = e.previous_instruction {
+                e.exec(inst);
+            } else {
+                e.status_bar.msg = "No previous command".into();
+                e.redraw_task = RedrawTask::StatusBar;
+            }
+        },
+    );
+
+    // Operators
+    r.register(Normal, "d", true, Motion, "Delete", |e, n| {
+        e.operator_pending(Operator::Delete, n.d())
+    });
+    r.register(
+        Normal,
+        "c",
+        true,
+        Motion,
+        "Delete and go to insert mode",
+        |e, n| e.operator_pending(Operator::Change, n.d()),
+    );
+    r.register(Normal, "y", true, Motion, "Yank to the register", |e, n| {
+        e.operator_pending(Operator::Yank, n.d())
+    });
+    r.register(Normal, ">", true, Motion, "Indent the lines", |e, n| {
+        e.operator_pending(Operator::ShiftRight, n.d())
+    });
+    r.register(Normal, "<", true, Motion, "Dedent the lines", |e, n| {
+        e.operator_pending(Operator::ShiftLeft, n.d())
+    });
+    r.register(Normal, "=", true, Motion, "Reindent the lines", |e, n| {
+        e.operator_pending(Operator::Reindent, n.d())
+    });
+    r.register(
+        Normal,
+        "!",
+        true,
+        Motion,
+        "Filter the lines through a shell command",
+        |e, n| e.operator_pending(Operator::Filter, n.d()),
+    );
+    r.register(Normal, "gu", true, Motion, "Make lowercase", |e, n| {
+        e.operator_pending(Operator::Lowercase, n.d())
+    });
+    r.register(Normal, "gU", true, Motion, "Make uppercase", |e, n| {
+        e.operator_pending(Operator::Uppercase, n.d())
+    });
+    r.register(Normal, "g~", true, Motion, "Toggle the case", |e, n| {
+        e.operator_pending(Operator::ToggleCase, n.d())
+    });
+    r.register(
+        Normal,
+        "gq",
+        true,
+        Motion,
+        "Format the lines to the text width",
+        |e, n| e.operator_pending(Operator::Format, n.d()),
+    );
+
+    // Visual mode
+    r.register(
+        Normal,
+        "v",
+        false,
+        NO_ARG,
+        "Start a visual selection",
+        |e, _| e.start_visual(VisualMode::Char),
+    );
+    r.register(
+        Normal,
+        "V",
+        false,
+        NO_ARG,
+        "Start a linewise visual selection",
+        |e, _| e.start_visual(VisualMode::Line),
+    );
+    r.register(Visual, "v", false, NO_ARG, "Select characters", |e, _| {
+        e.start_visual(VisualMode::Char)
+    });
+    r.register(Visual, "V", false, NO_ARG, "Select lines", |e, _| {
+        e.start_visual(VisualMode::Line)
+    });
+    r.register(
+        Visual,
+        "<Esc>",
+        false,
+        NO_ARG,
+        "Go to normal mode",
+        |e, _| {
+            e.cursor_mut().mode = Mode::Command(CommandMode::Normal);
+            e.redraw_task = RedrawTask::Full;
+        },
+    );
+    r.register(
+        Visual,
+        "o",
+        false,
+        NO_ARG,
+        "Go to the other end of the selection",
+        |e, _| e.swap_visual_ends(),
+    );
+    r.register(
+        Visual,
+        "d",
+        false,
+        NO_ARG,
+        "Delete the selection",
+        |e, _| e.visual_operator(Operator::Delete, 1),
+    );
+    r.register(
+        Visual,
+        "x",
+        false,
+        NO_ARG,
+        "Delete the selection",
+        |e, _| e.visual_operator(Operator::Delete, 1),
+    );
+    r.register(
+        Visual,
+        "c",
+        false,
+        NO_ARG,
+        "Delete the selection and go to insert mode",
+        |e, _| e.visual_operator(Operator::Change, 1),
+    );
+    r.register(Visual, "y", false, NO_ARG, "Yank the selection", |e, _| {
+        e.visual_operator(Operator::Yank, 1)
+    });
+    r.register(
+        Visual,
+        ">",
+        true,
+        NO_ARG,
+        "Indent the selected lines",
+        |e, n| e.visual_operator(Operator::ShiftRight, n.d()),
+    );
+    r.register(
+        Visual,
+        "<",
+        true,
+        NO_ARG,
+        "Dedent the selected lines",
+        |e, n| e.visual_operator(Operator::ShiftLeft, n.d()),
+    );
+    r.register(
+        Visual,
+        "=",
+        false,
+        NO_ARG,
+        "Reindent the selected lines",
+        |e, _| e.visual_operator(Operator::Reindent, 1),
+    );
+    r.register(Visual, "u", false, NO_ARG, "Make lowercase", |e, _| {
+        e.visual_operator(Operator::Lowercase, 1)
+    });
+    r.register(Visual, "U", false, NO_ARG, "Make uppercase", |e, _| {
+        e.visual_operator(Operator::Uppercase, 1)
+    });
+    r.register(Visual, "~", false, NO_ARG, "Toggle case", |e, _| {
+        e.visual_operator(Operator::ToggleCase, 1)
+    });
+    r.register(
+        Visual,
+        "gq",
+        false,
+        NO_ARG,
+        "Format the selected lines to the text width",
+        |e, _| e.visual_operator(Operator::Format, 1),
+    );
+    r.register(
+        Visual,
+        "!",
+        false,
+        NO_ARG,
+        "Filter the selected lines through a shell command",
+        |e, _| e.visual_operator(Operator::Filter, 1),
+    );
+
+    // Insert mode
+    r.register(
+        Insert,
+        "<Esc>",
+        false,
+        NO_ARG,
+        "Go to normal mode",
+        leave_insert,
+    );
+    r.register(
+        Insert,
+        "<S-Space>",
+        false,
+        NO_ARG,
+        "Go to normal mode",
+        leave_insert,
+    );
+
+    // Prompt mode
+    r.register(
+        Prompt,
+        "<S-Space>",
+        false,
+        NO_ARG,
+        "Leave the prompt without invoking the command",
+        |e, _| {
+            e.prompt.insert(0, String::new());
+            e.filter_range = None;
+            e.cursor_mut().mode = Mode::Command(CommandMode::Normal);
+        },
+    );
+    r.register(
+        Prompt,
+        "<CR>",
+        false,
+        NO_ARG,
+        "Invoke the command",
+        |e, _| e.submit_prompt(),
+    );
+    r.register(
+        Prompt,
+        "<BS>",
+        false,
+        NO_ARG,
+        "Delete the last char",
+        |e, _| {
+            let ix = e.prompt_index;
+            e.prompt[ix].pop();
+            e.redraw_task = RedrawTask::StatusBar;
+        },
+    );
+    r.register(
+        Prompt,
+        "<Up>",
+        false,
+        NO_ARG,
+        "Go to the previous command in the history",
+        |e, _| {
+            if e.prompt_index < e.prompt.len() - 1 {
+                e.prompt_index += 1;
+            }
+            e.redraw_task = RedrawTask::StatusBar;
+        },
+    );
+    r.register(
+        Prompt,
+        "<Down>",
+        false,
+        NO_ARG,
+        "Go to the next command in the history",
+        |e, _| {
+            if e.prompt_index > 0 {
+                e.prompt_index -= 1;
+            }
+            e.redraw_task = RedrawTask::StatusBar;
+        },
+    );
+
+    r
+}
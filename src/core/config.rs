@@ -0,0 +1,158 @@
+use core::prompt::PromptCommand;
+use state::editor::Editor;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+
+/// The maximal nesting of sourced files, to catch files sourcing themselves.
+pub const MAX_SOURCE_DEPTH: usize = 16;
+
+/// Get the configuration directory, `~/.config/sodium` (or the same directory under
+/// `$XDG_CONFIG_HOME`, if set).
+pub fn config_dir() -> Option<String> {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(ref dir) if !dir.is_empty() => Some(format!("{}/sodium", dir)),
+        _ => env::var("HOME")
+            .ok()
+            .map(|home| format!("{}/.config/sodium", home)),
+    }
+}
+
+/// Get the path of the default configuration file, `sodiumrc` in the configuration directory.
+pub fn default_config_path() -> Option<String> {
+    config_dir().map(|dir| format!("{}/sodiumrc", dir))
+}
+
+/// Expand a leading `~` in a path to the home directory.
+fn expand_home(path: &str) -> String {
+    match (path.starts_with("~/"), env::var("HOME")) {
+        (true, Ok(home)) => format!("{}{}", home, &path[1..]),
+        _ => path.to_owned(),
+    }
+}
+
+impl Editor {
+    /// Source a file, i.e. invoke every line of it as a prompt command. Empty lines and lines
+    /// starting with `"` or `#` (comments) are skipped.
+    ///
+    /// Every line is invoked, even if a previous line failed. The errors are returned together,
+    /// prefixed with the file and line number.
+    pub fn source(&mut self, path: &str) -> Result<(), String> {
+        let path = expand_home(path);
+
+        let mut con = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                if file.read_to_string(&mut con).is_err() {
+                    return Err(format!("Couldn't read {}", path));
+                }
+            }
+            Err(_) => return Err(format!("File {} could not be opened", path)),
+        }
+
+        self.source_str(&path, &con)
+    }
+
+    /// Invoke every line of a string as a prompt command, as if it was a sourced file with a
+    /// given name.
+    pub fn source_str(&mut self, path: &str, con: &str) -> Result<(), String> {
+        if self.source_depth >= MAX_SOURCE_DEPTH {
+            return Err(format!("{}: Files are sourced too deeply", path));
+        }
+        self.source_depth += 1;
+
+        let mut errors = Vec::new();
+        for (n, line) in con.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('"') || line.starts_with('#') {
+                continue;
+            }
+
+            let res = match PromptCommand::parse(line) {
+                Some(cmd) => self.invoke(cmd),
+                None => Err(format!("Unknown command: {}", line)),
+            };
+            if let Err(msg) = res {
+                errors.push(format!("{}:{}: {}", path, n + 1, msg));
+            }
+        }
+
+        self.source_depth -= 1;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Load the configuration file given by `-u`, or the default configuration file if none is
+    /// given. `-u NONE` skips loading any configuration. A missing default file is not an error.
+    pub fn load_config(&mut self, path: Option<String>) {
+        let res = match path {
+            Some(ref path) if path == "NONE" => Ok(()),
+            Some(path) => self.source(&path),
+            None => match default_config_path() {
+                Some(ref path) if File::open(path).is_ok() => self.source(path),
+                _ => Ok(()),
+            },
+        };
+
+        if let Err(msg) = res {
+            self.status_bar.msg = msg;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "orbital")))]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn errors_are_collected_with_file_and_line() {
+        let mut editor = Editor::new();
+        let con = "\" A comment\nset noautoindent\nfoo bar\n\n# Another one\nset line_numbers";
+        assert_eq!(
+            editor.source_str("rc", con),
+            Err("rc:3: Unknown command: foo bar".to_owned())
+        );
+        // The other lines are still invoked
+        assert!(!editor.bool_option("autoindent"));
+        assert!(editor.bool_option("line_numbers"));
+
+        let err = editor.source_str("rc", "foo\nbar").unwrap_err();
+        assert_eq!(
+            err,
+            "rc:1: Unknown command: foo; rc:2: Unknown command: bar"
+        );
+    }
+
+    #[test]
+    fn files_sourcing_themselves_are_stopped() {
+        let path = env::temp_dir().join(format!("sodium-test-{}.rc", process::id()));
+        let path = path.to_str().unwrap().to_owned();
+        fs::write(&path, format!("source {}\n", path)).unwrap();
+
+        let mut editor = Editor::new();
+        let err = editor.source(&path).unwrap_err();
+        let _ = fs::remove_file(&path);
+
+        let prefix = format!("{}:1: ", path);
+        assert!(err.starts_with(&prefix.repeat(MAX_SOURCE_DEPTH)));
+        assert!(err.ends_with(&format!("{}: Files are sourced too deeply", path)));
+        assert_eq!(editor.source_depth, 0);
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let mut editor = Editor::new();
+        assert_eq!(
+            editor.source("/nonexistent/sodiumrc"),
+            Err("File /nonexistent/sodiumrc could not be opened".to_owned())
+        );
+    }
+}
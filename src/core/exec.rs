@@ -1,360 +1,139 @@
-use core::prompt::PromptCommand;
-use edit::buffer::TextBuffer;
-use edit::insert::{InsertMode, InsertOptions};
-use io::parse::{Inst, Parameter};
+use core::registry::{Handler, Scope};
+use io::key::{Cmd, Key};
+use io::parse::Inst;
 use io::redraw::RedrawTask;
 use state::editor::Editor;
 use state::mode::{CommandMode, Mode, PrimitiveMode};
 
-// TODO: Move the command definitions outta here
+use std::time::Duration;
+
 impl Editor {
-    /// Execute an instruction
+    /// Execute an instruction, looking up its command in the command registry. Multi-key
+    /// commands read the rest of their keys from the input.
     pub fn exec(&mut self, Inst(para, cmd): Inst) {
-        use io::key::Key::*;
-        use state::mode::CommandMode::*;
-        use state::mode::Mode::*;
-        use state::mode::PrimitiveMode::*;
-
-        let n = para.d();
         let bef = self.pos();
-        let mut mov = false;
+        let scope = Scope::of(self.cursor().mode);
 
-        match (self.cursor().mode, cmd.key) {
-            (Primitive(Prompt), Char(' ')) if self.key_state.shift => {
-                self.prompt.insert(0, String::new());
-                self.cursor_mut().mode = Mode::Command(CommandMode::Normal);
-            }
-            (Primitive(Insert(_)), Escape) => {
-                let left = self.left(1);
-                self.goto(left);
-                self.cursor_mut().mode = Mode::Command(CommandMode::Normal);
-            }
-            (Primitive(Insert(_)), Char(' ')) if self.key_state.shift => {
-                let left = self.left(1);
-                self.goto(left);
-                self.cursor_mut().mode = Mode::Command(CommandMode::Normal);
-            }
-            (_, Char(' ')) if self.key_state.shift => {
-                self.cursor_mut().mode = Mode::Command(CommandMode::Normal)
-            }
-            (_, Char(' ')) if self.key_state.alt => self.next_cursor(),
-            _ if self.key_state.alt => {
-                if let Some(m) = self.to_motion(Inst(para, cmd)) {
-                    self.goto(m);
-                }
-            }
-            (Command(Normal), Char('i')) => {
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                    mode: InsertMode::Insert,
-                }));
-            }
-            (Command(Normal), Char('I')) => {
-                self.cursor_mut().x = 0;
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                    mode: InsertMode::Insert,
-                }));
-            }
-            (Command(Normal), Char('a')) => {
-                let pos = self.right(1, false);
-                self.goto(pos);
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                    mode: InsertMode::Insert,
-                }));
-            }
-            (Command(Normal), Char('A')) => {
-                let pos = (self.buffers.current_buffer()[self.y()].len(), self.y());
-                //let pos = self.right(1, false);
-                self.goto(pos);
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                    mode: InsertMode::Insert,
-                }));
-            }
-            (Command(Normal), Char('o')) => {
-                let y = self.y();
-                let ind = if self.options.autoindent {
-                    self.buffers.current_buffer().get_indent(y).to_owned()
-                } else {
-                    String::new()
-                };
-                let last = ind.len();
-                self.buffers
-                    .current_buffer_mut()
-                    .insert_line(y + 1, ind.into());
-                self.goto((last, y + 1));
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                    mode: InsertMode::Insert,
-                }));
-            }
-            (Command(Normal), Left) | (Command(Normal), Char('h')) => {
-                let left = self.left(n);
-                self.goto(left);
-                mov = true;
-            }
-            (Command(Normal), Down) | (Command(Normal), Char('j')) => {
-                let down = self.down(n);
-                self.goto(down);
-                mov = true;
-            }
-            (Command(Normal), Up) | (Command(Normal), Char('k')) => {
-                let up = self.up(n);
-                self.goto(up);
-                mov = true;
-            }
-            (Command(Normal), Right) | (Command(Normal), Char('l')) => {
-                let right = self.right(n, true);
-                self.goto(right);
-                mov = true;
-            }
-            (Command(Normal), Char('w')) => {
-                let next_word = self.next_word(n, true);
-                self.goto(next_word);
-                mov = true;
-            }
-            (Command(Normal), Char('e')) => {
-                let next_word = self.next_word_end(n, true);
-                self.goto(next_word);
-                mov = true;
-            }
-            (Command(Normal), Char('J')) => {
-                let down = self.down(15 * n);
-                self.goto(down);
-                mov = true;
-            }
-            (Command(Normal), Char('K')) => {
-                let up = self.up(15 * n);
-                self.goto(up);
-                mov = true;
-            }
-            (Command(Normal), Char('x')) => {
-                self.delete();
-                let bounded = self.bound(self.pos(), true);
-                self.goto(bounded);
-            }
-            (Command(Normal), Char('X')) => {
-                self.backspace();
-                let bounded = self.bound(self.pos(), true);
-                self.goto(bounded);
-            }
-            (Command(Normal), Char('L')) => {
-                if self.buffers.current_buffer()[self.y()].len() != 0 {
-                    let ln_end = (self.buffers.current_buffer()[self.y()].len() - 1, self.y());
-                    self.goto(ln_end);
-                    mov = true;
-                }
-            }
-            (Command(Normal), Char('$')) => {
-                if self.buffers.current_buffer()[self.y()].len() != 0 {
-                    let ln_end = (self.buffers.current_buffer()[self.y()].len() - 1, self.y());
-                    self.goto(ln_end);
-                    mov = true;
-                }
-            }
-            (Command(Normal), Char('H')) => {
-                println!("H pressed");
-                self.cursor_mut().x = 0;
-                mov = true;
-            }
-            (Command(Normal), Char('0')) => {
-                println!("0 pressed");
-                self.cursor_mut().x = 0;
-                mov = true;
-            }
-            (Command(Normal), Char('r')) => {
-                let (x, y) = self.pos();
-                let c = self.get_char();
-                let current_buffer = self.buffers.current_buffer_info_mut();
-                // If there is nothing in the current buffer
-                // ignore the command
-                if current_buffer.raw_buffer[y].len() > 0 {
-                    current_buffer.raw_buffer[y].remove(x);
-                }
-                current_buffer.raw_buffer[y].insert(x, c);
-            }
-            (Command(Normal), Char('R')) => {
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                    mode: InsertMode::Replace,
-                }));
-            }
-            (Command(Normal), Char('d')) => {
-                let ins = self.get_inst();
-                if let Some(m) = self.to_motion_unbounded(ins) {
-                    self.remove_rb(m);
-                }
-            }
-            (Command(Normal), Char('c')) => {
-                let ins = self.get_inst();
-                if let Some(m) = self.to_motion_unbounded(ins) {
-                    self.remove_rb(m);
-                    self.cursor_mut().mode =
-                        Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
-                            mode: InsertMode::Insert,
-                        }));
-                }
-            }
-            (Command(Normal), Char('G')) => {
-                let last = self.buffers.current_buffer().len() - 1;
-                self.goto((0, last));
-                mov = true;
-            }
-            (Command(Normal), Char('g')) => {
-                if let Parameter::Int(n) = para {
-                    self.goto((0, n.wrapping_sub(1)));
-                    mov = true;
-                } else {
-                    let inst = self.get_inst();
-                    if let Some(m) = self.to_motion(inst) {
-                        self.goto(m); // fix
-                        mov = true;
-                    }
-                }
-            }
-            (Command(Normal), Char('b')) => {
-                // Branch cursor
-                if self.buffers.current_buffer_info().cursors.len() < 255 {
-                    let cursor = self.cursor().clone();
-                    let current_cursor_index =
-                        self.buffers.current_buffer_info().current_cursor as usize;
-                    self.buffers
-                        .current_buffer_info_mut()
-                        .cursors
-                        .insert(current_cursor_index, cursor);
-                    self.next_cursor();
-                } else {
-                    self.status_bar.msg = format!("At max 255 cursors");
+        let mut keys = vec![cmd];
+        // The longest command matched so far, and the number of keys it consumed
+        let mut fallback: Option<(Handler, usize)> = None;
+
+        let handler = loop {
+            // A numeral is only passed on to longer commands taking one (e.g. `<numeral>gU`),
+            // otherwise the shorter command gets it (e.g. `<numeral>g`)
+            let (exact, longer) = self.commands.lookup(scope, &keys, para.is_int());
+            let exact = exact.map(|c| c.handler);
+
+            if let Some(handler) = exact {
+                if !longer {
+                    break Some(handler);
                 }
             }
-            (Command(Normal), Char('B')) => {
-                // Delete cursor
-                if self.buffers.current_buffer_info().cursors.len() > 1 {
-                    let current_cursor_index = self.buffers.current_buffer_info().current_cursor;
-                    self.buffers
-                        .current_buffer_info_mut()
-                        .cursors
-                        .remove(current_cursor_index as usize);
-                    self.prev_cursor();
-                } else {
-                    self.status_bar.msg = format!("No other cursors!");
-                }
+
+            if !longer {
+                break self.resolve_keys(&keys, fallback);
             }
-            (Command(Normal), Char('t')) => {
-                let ch = self.get_char();
 
-                let pos = self.next_ocur(ch, n);
-                if let Some(p) = pos {
-                    let y = self.y();
-                    self.goto((p, y));
-                    mov = true;
-                }
+            if let Some(handler) = exact {
+                fallback = Some((handler, keys.len()));
             }
-            (Command(Normal), Char('f')) => {
-                let ch = self.get_char();
 
-                let pos = self.previous_ocur(ch, n);
-                if let Some(p) = pos {
-                    let y = self.y();
-                    self.goto((p, y));
-                    mov = true;
+            // Like mappings, a complete command only waits `timeoutlen` for a longer one (e.g.
+            // `<numeral>g` for `<numeral>gU`)
+            if fallback.is_some() && self.pending_keys.is_empty() {
+                let timeout = Duration::from_millis(self.int_option("timeoutlen") as u64);
+                if !self.read_keys(Some(timeout)) {
+                    break self.resolve_keys(&keys, fallback);
                 }
             }
-            // (Command(Normal), Char('W')) => {
-            //     let pos = self.next_word_forward(n);
-            //     if let Some(p) = pos {
-            //         let y = self.y();
-            //         self.goto((p, y));
-            //         mov = true;
-            //     }
-            // }
-            (Command(Normal), Char(';')) => {
-                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Prompt)
+
+            let inst = self.get_inst();
+            if inst.0.is_int() || inst.1.key == Key::Null {
+                // A count (or no input) ends the key sequence
+                self.unread_inst(inst);
+                break self.resolve_keys(&keys, fallback);
+            }
+            keys.push(inst.1);
+        };
+
+        match handler {
+            Some(handler) => handler(self, para),
+            None => self.exec_default(Inst(para, cmd)),
+        }
+
+        if let RedrawTask::None = self.redraw_task {
+            if self.pos() != bef {
+                self.redraw_task = RedrawTask::Cursor(bef, self.pos());
             }
-            (Command(Normal), Char(' ')) => self.next_cursor(),
-            (Command(Normal), Char('z')) => {
-                let Inst(param, cmd) = self.get_inst();
-                match param {
-                    Parameter::Null => {
-                        if let Some(m) = self.to_motion(Inst(param, cmd)) {
-                            self.buffers.current_buffer_info_mut().scroll_y = m.1;
-                            self.goto(m);
-                        }
-                    }
-                    Parameter::Int(n) => {
-                        self.buffers.current_buffer_info_mut().scroll_y = n;
-                    }
+        }
+
+        if !(self.cursor().mode == Mode::Command(CommandMode::Normal) && cmd.key == Key::Char('.'))
+        {
+            self.previous_instruction = Some(Inst(para, cmd));
+        }
+    }
+
+    /// Resolve a key sequence to the longest command matched, given with the number of keys it
+    /// consumed. The keys after it are pushed back to the input.
+    fn resolve_keys(
+        &mut self,
+        keys: &[Cmd],
+        fallback: Option<(Handler, usize)>,
+    ) -> Option<Handler> {
+        let consumed = fallback.map_or(1, |(_, n)| n);
+        self.unread_keys(&keys[consumed..]);
+        fallback.map(|(h, _)| h)
+    }
+
+    /// Execute an instruction, which is not bound to any command.
+    fn exec_default(&mut self, Inst(para, cmd): Inst) {
+        match (self.cursor().mode, cmd.key) {
+            _ if cmd.alt => {
+                if let Some(m) = self.to_motion(Inst(para, cmd)) {
+                    self.goto(m);
                 }
-                self.redraw_task = RedrawTask::Full;
             }
-            (Command(Normal), Char('Z')) => {
-                self.buffers.current_buffer_info_mut().scroll_y = self.y() - 3;
-                self.redraw_task = RedrawTask::Full;
-            }
-            (Command(Normal), Char('~')) => {
-                self.invert_chars(n);
-            }
-            (Command(Normal), Char('.')) => {
-                if let Some(inst) = self.previous_instruction {
-                    self.exec(inst);
+            (Mode::Primitive(PrimitiveMode::Insert(opt)), k) => self.insert(k, opt),
+            // Any motion extends the visual selection
+            (Mode::Command(CommandMode::Visual(_)), _) => {
+                if let Some(m) = self.to_motion(Inst(para, cmd)) {
+                    self.goto(m);
                 } else {
-                    self.status_bar.msg = "No previous command".into();
+                    self.status_bar.msg = format!("Unknown command: {}", cmd.to_notation());
                     self.redraw_task = RedrawTask::StatusBar;
                 }
             }
-            (Command(Normal), Char(c)) => {
-                self.status_bar.msg = format!("Unknown command: {}", c);
-                self.redraw_task = RedrawTask::StatusBar;
-            }
-            (Primitive(Insert(opt)), k) => self.insert(k, opt),
-            (Primitive(Prompt), Char('\n')) => {
-                self.cursor_mut().mode = Command(Normal);
-                if let Some(cmd) = PromptCommand::parse(&self.prompt[self.prompt_index].clone()) {
-                    self.invoke(cmd);
-                    self.redraw_task = RedrawTask::StatusBar;
-                } else {
-                    self.status_bar.msg =
-                        format!("Unknown command: {}", self.prompt[self.prompt_index]);
-                }
-
-                // If we use a command we used before, don't add a new line to the vec
-                let cmd = self.prompt[self.prompt_index].clone();
-                if self.prompt_index != 0 {
-                    self.prompt[0] = cmd;
-                }
-                // Don't insert anything if the user didn't write anything
-                if self.prompt[self.prompt_index] != "" {
-                    self.prompt.insert(0, String::new());
-                }
-                self.prompt_index = 0;
-            }
-            (Primitive(Prompt), Backspace) => {
-                self.prompt[self.prompt_index].pop();
-                self.redraw_task = RedrawTask::StatusBar;
-            }
-            (Primitive(Prompt), Up) => {
-                if self.prompt_index < self.prompt.len() - 1 {
-                    self.prompt_index += 1;
-                }
-                self.redraw_task = RedrawTask::StatusBar;
-            }
-            (Primitive(Prompt), Down) => {
-                if self.prompt_index > 0 {
-                    self.prompt_index -= 1;
-                }
-                self.redraw_task = RedrawTask::StatusBar;
-            }
-            (Primitive(Prompt), Char(c)) => {
-                self.prompt[self.prompt_index].push(c);
+            (Mode::Primitive(PrimitiveMode::Prompt), Key::Char(c)) => {
+                let ix = self.prompt_index;
+                self.prompt[ix].push(c);
                 self.redraw_task = RedrawTask::StatusBar;
             }
             _ => {
-                self.status_bar.msg = format!("Unknown command");
+                self.status_bar.msg = format!("Unknown command: {}", cmd.to_notation());
                 self.redraw_task = RedrawTask::StatusBar;
             }
         }
-        if mov {
-            self.redraw_task = RedrawTask::Cursor(bef, self.pos());
-        }
+    }
+}
 
-        if !(self.cursor().mode == Command(Normal) && cmd.key == Char('.')) {
-            self.previous_instruction = Some(Inst(para, cmd));
-        }
+#[cfg(all(test, not(feature = "orbital")))]
+mod tests {
+    use super::*;
+    use edit::buffer::{SplitBuffer, TextBuffer};
+    use io::parse::Parameter;
+
+    #[test]
+    fn numeral_g_does_not_wait_for_other_keys() {
+        let mut editor = Editor::new();
+        *editor.buffers.current_buffer_mut() = SplitBuffer::from_str("a\nb\nc\nd\ne\nf");
+        editor.pending_keys.push_back(Cmd::new(Key::Char('x')));
+
+        editor.exec(Inst(Parameter::Int(5), Cmd::new(Key::Char('g'))));
+        assert_eq!(editor.pos(), (0, 4));
+        assert_eq!(editor.get_key(), Cmd::new(Key::Char('x')));
+
+        // Without further input, the command is resolved once `timeoutlen` has elapsed
+        editor.exec(Inst(Parameter::Int(2), Cmd::new(Key::Char('g'))));
+        assert_eq!(editor.pos(), (0, 1));
     }
 }
@@ -2,8 +2,17 @@
 #[macro_use]
 pub mod debug;
 
+/// The built-in command definitions.
+pub mod commands;
+
+/// Configuration files.
+pub mod config;
+
 /// Executing commands.
 pub mod exec;
 
 /// The command prompt.
 pub mod prompt;
+
+/// The command registry.
+pub mod registry;
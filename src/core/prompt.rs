@@ -1,16 +1,26 @@
 use edit::buffer::{SplitBuffer, TextBuffer};
 use io::file::FileStatus;
+use io::key::parse_keys;
+use io::mapping::MapMode;
 use io::redraw::RedrawTask;
+use io::theme::THEMES;
+use state::cursor::Cursor;
 use state::editor::{Buffer, BufferManager, Editor};
+use state::mode::{CommandMode, Mode};
+use state::options::{OptionValue, SetTarget};
 
 use std::process::exit;
 
+const HELP: &str = include_str!("../../help.txt");
+
 /// Prompt mode commands.
 pub enum PromptCommand<'a> {
-    /// Set an option.
+    /// Set options.
     Set {
-        /// The option to set.
+        /// The options to set, in the syntax of `Editor::apply_option`.
         option: &'a str,
+        /// The values to set.
+        target: SetTarget,
     },
     /// Unset an option.
     Unset {
@@ -37,8 +47,20 @@ pub enum PromptCommand<'a> {
         /// The path to write to.
         path: &'a str,
     },
+    /// Search for a pattern.
+    Search {
+        /// The pattern to search for.
+        pattern: &'a str,
+    },
+    /// Filter lines through an external command.
+    Filter {
+        /// The shell command to run.
+        command: &'a str,
+    },
     /// List the available buffers.
     ListBuffers,
+    /// List the cursors of the current buffer.
+    ListCursors,
     /// Create a new empty buffer.
     CreateBuffer,
     /// Delete the current buffer.
@@ -48,12 +70,66 @@ pub enum PromptCommand<'a> {
         /// The index of the buffer to switch to.
         buffer_index: usize,
     },
+    /// Describe the commands bound to a key sequence.
+    Describe {
+        /// The key sequence, in key notation.
+        keys: &'a str,
+    },
+    /// Map a key sequence to another key sequence.
+    Map {
+        /// The modes to map in.
+        modes: &'static [MapMode],
+        /// The keys to map.
+        lhs: &'a str,
+        /// The keys to map to.
+        rhs: &'a str,
+        /// Don't remap the keys mapped to.
+        noremap: bool,
+    },
+    /// Remove a mapping.
+    Unmap {
+        /// The modes to unmap in.
+        modes: &'static [MapMode],
+        /// The mapped keys.
+        lhs: &'a str,
+    },
+    /// List the mappings.
+    ListMappings {
+        /// The modes to list the mappings of.
+        modes: &'static [MapMode],
+        /// List only the mappings starting with these keys.
+        prefix: &'a str,
+    },
+    /// Add a command invoked when the filetype of a buffer is set.
+    Autocmd {
+        /// The event, which must be `FileType`.
+        event: &'a str,
+        /// The filetypes (comma separated), or `*` for every filetype.
+        pattern: &'a str,
+        /// The command to invoke.
+        command: &'a str,
+    },
+    /// Set or show the colors of a highlight group, or list every group if no group is given.
+    Highlight {
+        /// The group, followed by the colors to set.
+        args: &'a str,
+    },
+    /// Load a color scheme, or show the current one if no name is given.
+    Colorscheme {
+        /// The name of the color scheme.
+        name: &'a str,
+    },
+    /// Invoke the commands of a file.
+    Source {
+        /// The path of the file.
+        path: &'a str,
+    },
     /// Display help in a new buffer.
     Help,
     /// Exit Sodium.
     Quit,
     /// Quit without saving
-    ForceQuit
+    ForceQuit,
 }
 
 impl<'a> PromptCommand<'a> {
@@ -67,18 +143,75 @@ impl<'a> PromptCommand<'a> {
         let sec_cmd = split.nth(0).unwrap_or("");
 
         Some(match base_cmd {
-            "set" => Set { option: sec_cmd },
+            "set" | "se" => Set {
+                option: s[base_cmd.len()..].trim(),
+                target: SetTarget::Both,
+            },
+            "setlocal" | "setl" => Set {
+                option: s[base_cmd.len()..].trim(),
+                target: SetTarget::Local,
+            },
+            "setglobal" | "setg" => Set {
+                option: s[base_cmd.len()..].trim(),
+                target: SetTarget::Global,
+            },
             "unset" => Unset { option: sec_cmd },
             "toggle" | "tog" => Toggle { option: sec_cmd },
             "get" => Get { option: sec_cmd },
             "o" | "open" => Open { path: sec_cmd },
             "w" | "write" => Write { path: sec_cmd },
             "ls" => ListBuffers,
+            "cursors" => ListCursors,
             "bn" => CreateBuffer,
             "bd" => DeleteBuffer,
             "h" | "help" => Help,
+            "describe" | "desc" => Describe {
+                keys: s[base_cmd.len()..].trim(),
+            },
+            "map" | "nmap" | "imap" | "vmap" | "noremap" | "nnoremap" | "inoremap" | "vnoremap" => {
+                let args = s[base_cmd.len()..].trim();
+                let (lhs, rhs) = match args.find(' ') {
+                    Some(i) => (&args[..i], args[i..].trim()),
+                    None => (args, ""),
+                };
+
+                if rhs.is_empty() {
+                    ListMappings {
+                        modes: map_modes(base_cmd),
+                        prefix: lhs,
+                    }
+                } else {
+                    Map {
+                        modes: map_modes(base_cmd),
+                        lhs,
+                        rhs,
+                        noremap: base_cmd.ends_with("noremap"),
+                    }
+                }
+            }
+            "unmap" | "nunmap" | "iunmap" | "vunmap" => Unmap {
+                modes: map_modes(base_cmd),
+                lhs: sec_cmd,
+            },
+            "so" | "source" => Source { path: sec_cmd },
+            "au" | "autocmd" => {
+                let mut args = s[base_cmd.len()..].trim().splitn(3, ' ');
+                Autocmd {
+                    event: args.next().unwrap_or(""),
+                    pattern: args.next().unwrap_or(""),
+                    command: args.next().unwrap_or("").trim(),
+                }
+            }
+            "hi" | "highlight" => Highlight {
+                args: s[base_cmd.len()..].trim(),
+            },
+            "colo" | "colorscheme" => Colorscheme { name: sec_cmd },
             "q" | "quit" => Quit,
             "q!" => ForceQuit,
+            _ if s.starts_with('/') => Search { pattern: &s[1..] },
+            _ if s.starts_with('!') => Filter {
+                command: s[1..].trim(),
+            },
             bn if bn.starts_with("b") => {
                 let rest: String = bn.chars().skip(1).collect();
 
@@ -96,42 +229,74 @@ impl<'a> PromptCommand<'a> {
 }
 
 impl Editor {
-    /// Invoke a command in the prompt
-    pub fn invoke<'a>(&mut self, cmd: PromptCommand<'a>) {
+    /// Invoke the command currently written in the prompt, and go back to normal mode.
+    pub fn submit_prompt(&mut self) {
+        self.cursor_mut().mode = Mode::Command(CommandMode::Normal);
+        if let Some(cmd) = PromptCommand::parse(&self.prompt[self.prompt_index].clone()) {
+            if let Err(msg) = self.invoke(cmd) {
+                self.status_bar.msg = msg;
+            }
+            // Keep any larger redraw requested by the command
+            if let RedrawTask::None = self.redraw_task {
+                self.redraw_task = RedrawTask::StatusBar;
+            }
+        } else {
+            self.status_bar.msg = format!("Unknown command: {}", self.prompt[self.prompt_index]);
+        }
+
+        // If we use a command we used before, don't add a new line to the vec
+        let cmd = self.prompt[self.prompt_index].clone();
+        if self.prompt_index != 0 {
+            self.prompt[0] = cmd;
+        }
+        // Don't insert anything if the user didn't write anything
+        if !self.prompt[self.prompt_index].is_empty() {
+            self.prompt.insert(0, String::new());
+        }
+        self.prompt_index = 0;
+        // A range given by `!` only applies to the command it opened the prompt for
+        self.filter_range = None;
+    }
+
+    /// Invoke a command in the prompt. Returns an error message, if the command failed.
+    pub fn invoke<'a>(&mut self, cmd: PromptCommand<'a>) -> Result<(), String> {
         use self::PromptCommand::*;
 
         match cmd {
-            Set { option } => {
-                self.status_bar.msg = match self.options.set(option) {
-                    Ok(()) => format!("Option set: {}", option),
-                    Err(()) => format!("Option does not exist: {}", option),
+            Set { option, target } => {
+                let mut msgs = Vec::new();
+                for arg in option.split_whitespace() {
+                    msgs.push(self.apply_option(arg, target).map_err(|e| e.to_string())?);
                 }
+                self.status_bar.msg = if msgs.is_empty() {
+                    self.describe_options(target)
+                } else {
+                    msgs.join(" ")
+                };
             }
             Unset { option } => {
-                self.status_bar.msg = match self.options.unset(option) {
-                    Ok(()) => format!("Option unset: {}", option),
-                    Err(()) => format!("Option does not exist: {}", option),
-                }
-            }
-            Toggle { option } => {
-                self.status_bar.msg = match self.options.toggle(option) {
-                    Ok(()) => format!("Option toggled: {}", option),
-                    Err(()) => format!("Option does not exist: {}", option),
+                match self.set_option(option, OptionValue::Bool(false), SetTarget::Both) {
+                    Ok(()) => self.status_bar.msg = format!("Option unset: {}", option),
+                    Err(e) => return Err(e.to_string()),
                 }
             }
+            Toggle { option } => match self.toggle_option(option, SetTarget::Both) {
+                Ok(()) => self.status_bar.msg = format!("Option toggled: {}", option),
+                Err(e) => return Err(e.to_string()),
+            },
             Get { option } => {
-                self.status_bar.msg = match self.options.get(option) {
-                    Some(true) => format!("Option set: {}", option),
-                    Some(false) => format!("Option unset: {}", option),
-                    None => format!("Option does not exist: {}", option),
-                }
+                self.status_bar.msg = self
+                    .show_option(option, SetTarget::Both)
+                    .map_err(|e| e.to_string())?;
             }
             Open { path } => {
                 let line = self.buffers.current_buffer().get_line(0).unwrap().len();
                 let empty = self.buffers.current_buffer().len() == 1 && line == 0;
                 let ix = self.buffers.current_buffer_index();
                 self.status_bar.msg = match self.open(path) {
-                    FileStatus::NotFound => format!("File {} could not be opened", path),
+                    FileStatus::NotFound => {
+                        return Err(format!("File {} could not be opened", path))
+                    }
                     FileStatus::Ok => format!("File {} opened", path),
                     _ => unreachable!(),
                 };
@@ -141,23 +306,33 @@ impl Editor {
             }
             Write { path } => {
                 // TODO: if open multiple files written write the current file
-                let mut write_path: String = if path.is_empty() {
+                let write_path: String = if path.is_empty() {
                     self.files[0].clone()
                 } else {
                     path.to_string()
                 };
 
-                if self.options.get("readonly") == Some(true) {
+                if self.bool_option("readonly") {
                     // TODO: add override (w!)
-                    self.status_bar.msg = format!("File {} is opened in readonly mode", write_path)
-                } else {
-                    self.status_bar.msg = match self.write(&mut write_path) {
-                        FileStatus::NotFound => format!("File {} could not be opened", write_path),
-                        FileStatus::Ok => format!("File {} written", write_path),
-                        FileStatus::Other => format!("Couldn't write {}", write_path),
+                    return Err(format!("File {} is opened in readonly mode", write_path));
+                }
+                self.status_bar.msg = match self.write(&write_path) {
+                    FileStatus::NotFound => {
+                        return Err(format!("File {} could not be opened", write_path))
                     }
+                    FileStatus::Ok => format!("File {} written", write_path),
+                    FileStatus::Other => return Err(format!("Couldn't write {}", write_path)),
                 }
             }
+            Search { pattern } => {
+                self.last_search = Some(pattern.to_owned());
+                self.search_next(1, true);
+            }
+            Filter { command } => {
+                let y = self.y();
+                let (sy, ey) = self.filter_range.take().unwrap_or((y, y));
+                self.filter_lines(sy, ey, command)?;
+            }
             ListBuffers => {
                 let description = get_buffers_description(&self.buffers);
                 let mut new_buffer: Buffer = SplitBuffer::from_str(&description).into();
@@ -168,11 +343,21 @@ impl Editor {
                 self.buffers.switch_to(new_buffer_index);
                 self.redraw_task = RedrawTask::Full;
             }
+            ListCursors => {
+                let description = get_cursors_description(self.buffers.current_buffer_info());
+                let mut new_buffer: Buffer = SplitBuffer::from_str(&description).into();
+                new_buffer.title = Some("<Cursors>".into());
+                new_buffer.is_transient = true; // delete the buffer when the user switches away
+
+                let new_buffer_index = self.buffers.new_buffer(new_buffer);
+                self.buffers.switch_to(new_buffer_index);
+                self.redraw_task = RedrawTask::Full;
+            }
             SwitchToBuffer { buffer_index: ix } => {
                 if !self.buffers.is_buffer_index_valid(ix) {
-                    self.status_bar.msg = format!("Invalid buffer #{}", ix);
+                    return Err(format!("Invalid buffer #{}", ix));
                 } else if self.buffers.current_buffer_index() == ix {
-                    self.status_bar.msg = format!("Already in buffer #{}", ix);
+                    return Err(format!("Already in buffer #{}", ix));
                 } else {
                     self.buffers.switch_to(ix);
                     self.redraw_task = RedrawTask::Full;
@@ -190,13 +375,91 @@ impl Editor {
                 self.buffers.delete_buffer(ix);
                 self.redraw_task = RedrawTask::Full;
             }
+            Describe { keys } => {
+                self.status_bar.msg = self.describe_keys(keys);
+            }
+            Map {
+                modes,
+                lhs,
+                rhs,
+                noremap,
+            } => {
+                self.mappings.map(modes, lhs, rhs, noremap)?;
+                self.status_bar.msg = format!("Mapped {} to {}", lhs, rhs);
+            }
+            Unmap { modes, lhs } => {
+                self.mappings.unmap(modes, lhs)?;
+                self.status_bar.msg = format!("Unmapped {}", lhs);
+            }
+            ListMappings { modes, prefix } => {
+                // Without a prefix every mapping is listed
+                let prefix = if prefix.is_empty() {
+                    Vec::new()
+                } else {
+                    parse_keys(prefix)?
+                };
+                let description = self.mappings.list(modes, &prefix);
+                let mut new_buffer: Buffer = SplitBuffer::from_str(&description).into();
+                new_buffer.title = Some("<Mappings>".into());
+                new_buffer.is_transient = true; // delete the buffer when the user switches away
+
+                let new_buffer_index = self.buffers.new_buffer(new_buffer);
+                self.buffers.switch_to(new_buffer_index);
+                self.redraw_task = RedrawTask::Full;
+            }
+            Source { path } => {
+                self.source(path)?;
+                self.status_bar.msg = format!("Sourced {}", path);
+            }
+            Autocmd {
+                event,
+                pattern,
+                command,
+            } => {
+                if !event.eq_ignore_ascii_case("filetype") {
+                    return Err(format!("Unknown event: {}", event));
+                }
+                if pattern.is_empty() || command.is_empty() {
+                    return Err("Usage: autocmd FileType <filetypes> <command>".to_owned());
+                }
+                self.filetype_hooks
+                    .push((pattern.to_owned(), command.to_owned()));
+            }
+            Highlight { args } => {
+                if args.is_empty() {
+                    self.list_highlight_groups();
+                } else {
+                    self.highlight_group(args)?;
+                }
+            }
+            Colorscheme { name } => {
+                if name.is_empty() {
+                    let names: Vec<&str> = THEMES.iter().map(|&(n, _)| n).collect();
+                    self.status_bar.msg = format!(
+                        "Color scheme: {} (bundled: {})",
+                        self.theme.name,
+                        names.join(", ")
+                    );
+                } else {
+                    self.colorscheme(name)?;
+                }
+            }
             Help => {
-                self.open("/apps/sodium/help.txt");
+                let help = format!("{}\n# Commands\n{}", HELP, self.commands.help());
+                let mut new_buffer: Buffer = SplitBuffer::from_str(&help).into();
+                new_buffer.title = Some("<Help>".into());
+                new_buffer.is_transient = true; // delete the buffer when the user switches away
+
+                let new_buffer_index = self.buffers.new_buffer(new_buffer);
+                self.buffers.switch_to(new_buffer_index);
+                self.redraw_task = RedrawTask::Full;
             }
             Quit => {
                 let is_buffer_dirty = self.buffers.current_buffer_info().dirty;
                 if is_buffer_dirty {
-                    self.status_bar.msg = format!("can not quit this file, there are some changes in the file");
+                    return Err(
+                        "can not quit this file, there are some changes in the file".to_owned()
+                    );
                 } else {
                     exit(0);
                 }
@@ -207,12 +470,23 @@ impl Editor {
         }
 
         self.hint();
+        Ok(())
+    }
+}
+
+/// Get the modes a map command (e.g. `nnoremap` or `unmap`) applies to.
+fn map_modes(command: &str) -> &'static [MapMode] {
+    match command.chars().next() {
+        Some('n') if command != "noremap" => &[MapMode::Normal],
+        Some('i') => &[MapMode::Insert],
+        Some('v') => &[MapMode::Visual],
+        _ => &[MapMode::Normal, MapMode::Visual],
     }
 }
 
 fn get_buffers_description(buffers: &BufferManager) -> String {
     fn print_buffer(i: usize, b: &Buffer) -> String {
-        let title = b.title.as_ref().map(|s| s.as_str()).unwrap_or("<No Title>");
+        let title = b.title.as_deref().unwrap_or("<No Title>");
 
         format!("b{}\t\t\t{}", i, title)
     }
@@ -232,3 +506,41 @@ fn get_buffers_description(buffers: &BufferManager) -> String {
         descriptions
     )
 }
+
+fn get_cursors_description(buffer: &Buffer) -> String {
+    let print_cursor = |i: usize, c: &Cursor| -> String {
+        let current = if i == buffer.current_cursor as usize {
+            "*"
+        } else {
+            " "
+        };
+        let preview = buffer
+            .raw_buffer
+            .get_line(c.y)
+            .map(|s| s.trim())
+            .unwrap_or("");
+
+        format!(
+            "{}c{}\t{}\t{}:{}\t\t{}",
+            current,
+            i,
+            c.mode.to_string(),
+            c.y + 1,
+            c.x + 1,
+            preview
+        )
+    };
+
+    let descriptions = buffer
+        .cursors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| print_cursor(i, c))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "Cursors\n=====================================\n\n{}",
+        descriptions
+    )
+}
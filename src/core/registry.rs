@@ -0,0 +1,221 @@
+use io::key::{parse_keys, Cmd};
+use io::parse::Parameter;
+use state::editor::Editor;
+use state::mode::{CommandMode, Mode, PrimitiveMode};
+
+use std::slice::Iter;
+
+/// A command handler. The parameter is the numeral given before the command.
+pub type Handler = fn(&mut Editor, Parameter);
+
+#[derive(Copy, Clone, PartialEq)]
+/// The set of modes a command is available in.
+pub enum Scope {
+    /// Every mode.
+    Global,
+    /// Normal mode.
+    Normal,
+    /// Visual mode.
+    Visual,
+    /// Insert (and replace) mode.
+    Insert,
+    /// Prompt mode.
+    Prompt,
+}
+
+impl Scope {
+    /// Get the scope of the commands, which are specific to a given mode.
+    pub fn of(mode: Mode) -> Scope {
+        match mode {
+            Mode::Command(CommandMode::Normal) => Scope::Normal,
+            Mode::Command(CommandMode::Visual(_)) => Scope::Visual,
+            Mode::Primitive(PrimitiveMode::Insert(_)) => Scope::Insert,
+            Mode::Primitive(PrimitiveMode::Prompt) => Scope::Prompt,
+        }
+    }
+
+    /// Are commands of this scope available in the mode of scope `mode`?
+    pub fn covers(self, mode: Scope) -> bool {
+        self == Scope::Global || self == mode
+    }
+
+    /// The name of the scope.
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::Global => "Global",
+            Scope::Normal => "Normal",
+            Scope::Visual => "Visual",
+            Scope::Insert => "Insert",
+            Scope::Prompt => "Prompt",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+/// The argument, which a command reads after its keys.
+pub enum Argument {
+    /// No argument.
+    None,
+    /// A motion (or text object).
+    Motion,
+    /// A single character.
+    Char,
+}
+
+/// A command, i.e. a key sequence bound to a handler in a given scope.
+pub struct Command {
+    /// The modes the command is available in.
+    pub scope: Scope,
+    /// The key sequence invoking the command.
+    pub keys: Vec<Cmd>,
+    /// Does the command take a numeral parameter?
+    pub count: bool,
+    /// The argument read after the keys.
+    pub argument: Argument,
+    /// A short description of the command.
+    pub description: &'static str,
+    /// The handler executing the command.
+    pub handler: Handler,
+}
+
+impl Command {
+    /// Get the usage of the command, e.g. `<numeral>t<char>`.
+    pub fn usage(&self) -> String {
+        let keys: String = self.keys.iter().map(|k| k.to_notation()).collect();
+
+        format!(
+            "{}{}{}",
+            if self.count { "<numeral>" } else { "" },
+            keys,
+            match self.argument {
+                Argument::None => "",
+                Argument::Motion => "<motion>",
+                Argument::Char => "<char>",
+            }
+        )
+    }
+}
+
+/// The command registry, holding the definitions of every command.
+pub struct Registry {
+    commands: Vec<Command>,
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+impl Registry {
+    /// Create a new empty registry.
+    pub fn new() -> Registry {
+        Registry {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Register a command. `keys` is given in key notation (see `io::key::parse_keys`).
+    ///
+    /// Panics if the key notation is invalid, as commands are defined statically.
+    pub fn register(
+        &mut self,
+        scope: Scope,
+        keys: &str,
+        count: bool,
+        argument: Argument,
+        description: &'static str,
+        handler: Handler,
+    ) {
+        let keys = parse_keys(keys).expect("Invalid key notation in command definition");
+
+        self.commands.push(Command {
+            scope,
+            keys,
+            count,
+            argument,
+            description,
+            handler,
+        });
+    }
+
+    /// Look up a (partial) key sequence in the mode of scope `mode`. Returns the command matching
+    /// the sequence exactly, if any, and whether longer commands starting with the sequence
+    /// exist. If `counted` is set, longer commands not taking a numeral are ignored.
+    ///
+    /// Mode specific commands take precedence over global commands.
+    pub fn lookup(&self, mode: Scope, keys: &[Cmd], counted: bool) -> (Option<&Command>, bool) {
+        let mut exact: Option<&Command> = None;
+        let mut longer = false;
+
+        for cmd in self
+            .commands
+            .iter()
+            .filter(|c| c.scope.covers(mode) && c.keys.starts_with(keys))
+        {
+            if cmd.keys.len() > keys.len() {
+                longer |= cmd.count || !counted;
+            } else if exact.is_none_or(|e| e.scope == Scope::Global) {
+                exact = Some(cmd);
+            }
+        }
+
+        (exact, longer)
+    }
+
+    /// Get every command bound to a key sequence, in any scope.
+    pub fn find(&self, keys: &[Cmd]) -> Vec<&Command> {
+        self.commands.iter().filter(|c| c.keys == keys).collect()
+    }
+
+    /// Get an iterator over the commands.
+    pub fn iter(&self) -> Iter<'_, Command> {
+        self.commands.iter()
+    }
+
+    /// Generate a reference of every command, grouped by scope.
+    pub fn help(&self) -> String {
+        let mut help = String::new();
+
+        for &scope in [
+            Scope::Global,
+            Scope::Normal,
+            Scope::Visual,
+            Scope::Insert,
+            Scope::Prompt,
+        ]
+        .iter()
+        {
+            help.push_str(&format!("\n# {}\n\n", scope.name()));
+            for cmd in self.commands.iter().filter(|c| c.scope == scope) {
+                help.push_str(&format!("- {} : {}\n", cmd.usage(), cmd.description));
+            }
+        }
+
+        help
+    }
+}
+
+impl Editor {
+    /// Describe the commands bound to a key sequence (given in key notation).
+    pub fn describe_keys(&self, keys: &str) -> String {
+        let keys = match parse_keys(keys) {
+            Ok(k) => k,
+            Err(e) => return e,
+        };
+
+        let descriptions: Vec<String> = self
+            .commands
+            .find(&keys)
+            .iter()
+            .map(|c| format!("{} ({}): {}", c.usage(), c.scope.name(), c.description))
+            .collect();
+
+        if descriptions.is_empty() {
+            let keys: String = keys.iter().map(|k| k.to_notation()).collect();
+            format!("No command bound to {}", keys)
+        } else {
+            descriptions.join("; ")
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::cmp::min;
 use std::ops::{Index, IndexMut};
 use std::str::Chars;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// A line in a buffer.
 pub trait Line<'a> {
@@ -19,6 +20,38 @@ impl<'a, T: AsRef<str>> Line<'a> for T {
     }
 }
 
+/// Get the number of grapheme clusters of a line, i.e. the number of cursor positions on it.
+pub fn grapheme_count(line: &str) -> usize {
+    line.graphemes(true).count()
+}
+
+/// Get the byte offset of the `x`th grapheme cluster of a line, or the length of the line if
+/// `x` is after its end.
+pub fn byte_index(line: &str, x: usize) -> usize {
+    line.grapheme_indices(true)
+        .nth(x)
+        .map_or(line.len(), |(i, _)| i)
+}
+
+/// Get the index of the grapheme cluster of a line, which contains a byte offset.
+pub fn grapheme_index(line: &str, byte: usize) -> usize {
+    line.grapheme_indices(true)
+        .take_while(|&(i, g)| i + g.len() <= byte)
+        .count()
+}
+
+/// Get the `x`th grapheme cluster of a line.
+pub fn grapheme_at(line: &str, x: usize) -> Option<&str> {
+    line.graphemes(true).nth(x)
+}
+
+/// Get an iterator over the first char of each grapheme cluster of a line, i.e. the char at
+/// each cursor position.
+pub fn grapheme_chars<'a>(line: &'a str) -> impl DoubleEndedIterator<Item = char> + 'a {
+    line.graphemes(true)
+        .map(|g| g.chars().next().unwrap_or(' '))
+}
+
 /// A buffer structure
 pub trait TextBuffer<'a> {
     /// The line type of the buffer.
@@ -64,6 +97,22 @@ pub trait TextBuffer<'a> {
 
     /// Get the leading whitespaces of the nth line. Used for autoindenting.
     fn get_indent(&self, n: usize) -> &str;
+
+    /// Get the first line changed (or inserted or removed) since the last call, if any, and
+    /// reset it.
+    fn take_damage(&mut self) -> Option<usize>;
+
+    /// Get the display width of the indentation of the nth line, with tabs expanded to the next
+    /// multiple of `tabstop`.
+    fn get_indent_width(&self, n: usize, tabstop: usize) -> usize {
+        self.get_indent(n).chars().fold(0, |width, c| {
+            if c == '\t' {
+                width + tabstop - width % tabstop
+            } else {
+                width + 1
+            }
+        })
+    }
 }
 
 /// The buffer data structure, that Sodium is using.
@@ -77,6 +126,8 @@ pub trait TextBuffer<'a> {
 pub struct SplitBuffer {
     before: Vec<String>,
     after: Vec<String>,
+    /// The first line changed since the damage was last taken
+    damage: Option<usize>,
     #[cfg(debug)]
     _hinted_since_edit: bool,
 }
@@ -95,6 +146,10 @@ impl SplitBuffer {
     fn y(&self) -> usize {
         self.before.len()
     }
+
+    fn damage(&mut self, n: usize) {
+        self.damage = Some(self.damage.map_or(n, |d| min(d, n)));
+    }
 }
 
 // TODO remove
@@ -113,6 +168,7 @@ impl<'a> TextBuffer<'a> for SplitBuffer {
         SplitBuffer {
             before: vec![String::new()],
             after: Vec::new(),
+            damage: None,
         }
     }
 
@@ -120,6 +176,7 @@ impl<'a> TextBuffer<'a> for SplitBuffer {
         SplitBuffer {
             before: s.lines().map(ToOwned::to_owned).collect(),
             after: Vec::new(),
+            damage: None,
         }
     }
 
@@ -135,6 +192,7 @@ impl<'a> TextBuffer<'a> for SplitBuffer {
     }
 
     fn get_line_mut(&mut self, n: usize) -> Option<&mut String> {
+        self.damage(n);
         if n < self.before.len() {
             Some(&mut self.before[n])
         } else if n < self.len() {
@@ -146,21 +204,19 @@ impl<'a> TextBuffer<'a> for SplitBuffer {
     }
 
     fn remove_line(&mut self, n: usize) -> String {
+        self.damage(n);
         if n < self.before.len() {
             self.before.remove(n)
         } else if n < self.len() {
-            let n = n - self.before.len();
-            let ret = self.after.remove(n);
-            if n == 0 {
-                self.up();
-            }
-            ret
+            let n = self.len() - 1 - n;
+            self.after.remove(n)
         } else {
             panic!("Out of bound");
         }
     }
 
     fn insert_line(&mut self, n: usize, line: String) {
+        self.damage(n);
         if n < self.before.len() {
             self.before.insert(n, line);
         } else if n <= self.len() {
@@ -175,6 +231,7 @@ impl<'a> TextBuffer<'a> for SplitBuffer {
         SplitBuffer {
             before: ln.to_owned(),
             after: Vec::new(),
+            damage: None,
         }
     }
 
@@ -226,6 +283,10 @@ impl<'a> TextBuffer<'a> for SplitBuffer {
             ""
         }
     }
+
+    fn take_damage(&mut self) -> Option<usize> {
+        self.damage.take()
+    }
 }
 
 impl Index<usize> for SplitBuffer {
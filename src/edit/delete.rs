@@ -1,4 +1,4 @@
-use edit::buffer::TextBuffer;
+use edit::buffer::{byte_index, grapheme_count, TextBuffer};
 use io::redraw::RedrawTask;
 use state::cursor::Cursor;
 use state::editor::Editor;
@@ -8,15 +8,19 @@ impl Editor {
     #[inline]
     pub fn delete(&mut self) {
         let &Cursor { x, y, .. } = self.cursor();
+        self.push_change();
         self.buffers.current_buffer_info_mut().dirty = true;
-        if x == self.buffers.current_buffer()[y].len() {
+        let len = grapheme_count(&self.buffers.current_buffer()[y]);
+        if x == len {
             if y + 1 < self.buffers.current_buffer().len() {
-                let s = self.buffers.current_buffer_mut().remove_line(y + 1);
+                let s = self.remove_line(y + 1);
                 self.buffers.current_buffer_mut()[y].push_str(&s);
                 self.redraw_task = RedrawTask::Lines(y..y + 1);
             }
-        } else if x < self.buffers.current_buffer()[y].len() {
-            self.buffers.current_buffer_mut()[y].remove(x);
+        } else if x < len {
+            let line = &self.buffers.current_buffer()[y];
+            let range = byte_index(line, x)..byte_index(line, x + 1);
+            self.buffers.current_buffer_mut()[y].drain(range);
             self.redraw_task = RedrawTask::LinesAfter(y);
         }
 
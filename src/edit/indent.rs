@@ -0,0 +1,357 @@
+use edit::buffer::TextBuffer;
+use io::redraw::RedrawTask;
+use state::editor::Editor;
+use state::options::{OptionValue, SetTarget};
+
+/// The largest indentation width, which is detected.
+const MAX_DETECTED_WIDTH: usize = 8;
+
+#[derive(Clone, PartialEq, Copy, Debug)]
+/// The indentation style of a file.
+pub enum IndentStyle {
+    /// Indentation by tabs.
+    Tabs,
+    /// Indentation by a given number of spaces per level.
+    Spaces(usize),
+}
+
+/// Scan the code of a line, calling `f` on every char outside of string and char literals, up
+/// to a trailing `//` comment. Returns the byte index where the comment starts (or the length of
+/// the line).
+fn scan_code<F: FnMut(char)>(line: &str, mut f: F) -> usize {
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if line[i + 1..].starts_with('/') => return i,
+            '"' => {
+                // Skip the string, including escaped quotes
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                // Skip char literals, but not lifetimes
+                let mut rest = chars.clone().map(|(_, c)| c);
+                match (rest.next(), rest.next()) {
+                    (Some('\\'), _) => {
+                        chars.next();
+                        chars.next();
+                        chars.find(|&(_, c)| c == '\'');
+                    }
+                    (Some(_), Some('\'')) => {
+                        chars.next();
+                        chars.next();
+                    }
+                    _ => {}
+                }
+            }
+            _ => f(c),
+        }
+    }
+
+    line.len()
+}
+
+/// Strip a trailing `//` comment from a line.
+fn strip_comment(line: &str) -> &str {
+    &line[..scan_code(line, |_| {})]
+}
+
+/// Check if a line opens a block, i.e. ends with `{`, `(`, `[` or `:` (ignoring trailing
+/// whitespace and comments).
+pub fn opens_block(line: &str) -> bool {
+    strip_comment(line)
+        .trim_end()
+        .ends_with(['{', '(', '[', ':'])
+}
+
+/// Get the brace balance of a line: The number of closing braces it starts with, and the number
+/// of opening minus the number of closing braces in it. Braces in string and char literals and
+/// in comments are not counted.
+fn brace_balance(line: &str) -> (usize, isize) {
+    let line = line.trim_start();
+    let lead = line.chars().take_while(|&c| "}])".contains(c)).count();
+
+    let mut net = 0;
+    scan_code(line, |c| match c {
+        '{' | '(' | '[' => net += 1,
+        '}' | ')' | ']' => net -= 1,
+        _ => {}
+    });
+
+    (lead, net)
+}
+
+/// Guess the indentation style of a file from the leading whitespace of its lines. Tabs are
+/// chosen if more lines are indented by tabs than by spaces. Otherwise the width is the most
+/// common change of indentation between consecutive indented lines.
+pub fn detect_indent<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Option<IndentStyle> {
+    let (mut tabs, mut spaces) = (0, 0);
+    // The number of times each width is the step between two lines
+    let mut steps = [0usize; MAX_DETECTED_WIDTH + 1];
+    let mut prev = 0;
+
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        if indent.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+        if indent.contains('\t') {
+            continue;
+        }
+
+        let width = indent.len();
+        // A single space is rather alignment (e.g. of ` * ` in comments) than indentation
+        if width != 1 {
+            if width > 0 {
+                spaces += 1;
+            }
+            let step = width.abs_diff(prev);
+            if step > 1 && step <= MAX_DETECTED_WIDTH {
+                steps[step] += 1;
+            }
+            prev = width;
+        }
+    }
+
+    if tabs == 0 && spaces == 0 {
+        None
+    } else if tabs > spaces {
+        Some(IndentStyle::Tabs)
+    } else {
+        // Ties go to the smaller width
+        (2..MAX_DETECTED_WIDTH + 1)
+            .filter(|&w| steps[w] > 0)
+            .max_by_key(|&w| (steps[w], MAX_DETECTED_WIDTH - w))
+            .map(IndentStyle::Spaces)
+    }
+}
+
+impl Editor {
+    /// Get the whitespace indenting a line to the display width `width`, using tabs (of
+    /// `tabstop` width) unless `expandtab` is set.
+    pub fn indent_string(&self, width: usize) -> String {
+        if self.bool_option("expandtab") {
+            " ".repeat(width)
+        } else {
+            let tabstop = self.int_option("tabstop");
+            "\t".repeat(width / tabstop) + &" ".repeat(width % tabstop)
+        }
+    }
+
+    /// Get the display width of the indentation of the nth line.
+    pub fn indent_width(&self, n: usize) -> usize {
+        self.buffers
+            .current_buffer()
+            .get_indent_width(n, self.int_option("tabstop"))
+    }
+
+    /// Shift the lines from `sy` to `ey` one `shiftwidth` right or left, `times` times. The
+    /// indentation is rounded to a multiple of `shiftwidth`, and redone with tabs or spaces
+    /// according to `expandtab`. Blank lines are not indented.
+    pub fn shift_lines(&mut self, sy: usize, ey: usize, right: bool, times: usize) {
+        let sw = self.int_option("shiftwidth");
+
+        for y in sy..ey + 1 {
+            if self.buffers.current_buffer()[y].trim().is_empty() {
+                continue;
+            }
+
+            let width = self.indent_width(y);
+            let width = if right {
+                (width / sw + times) * sw
+            } else {
+                // Round up to a multiple first, such that the first shift goes to the previous
+                // multiple
+                width.div_ceil(sw).saturating_sub(times) * sw
+            };
+
+            let indent = self.indent_string(width);
+            let old_len = self.buffers.current_buffer().get_indent(y).len();
+            self.buffers.current_buffer_mut()[y].replace_range(..old_len, &indent);
+        }
+
+        self.buffers.current_buffer_info_mut().dirty = true;
+        self.redraw_task = RedrawTask::LinesAfter(sy);
+    }
+
+    /// Get the indentation for a new line following the nth line: The indentation of the nth
+    /// line if `autoindent` is set, otherwise nothing. With `smartindent`, a level is added after
+    /// a line opening a block.
+    pub fn auto_indent(&self, n: usize) -> String {
+        let smart = self.bool_option("smartindent");
+        if !self.bool_option("autoindent") && !smart {
+            return String::new();
+        }
+
+        let mut width = self.indent_width(n);
+        if smart && opens_block(&self.buffers.current_buffer()[n]) {
+            width += self.int_option("shiftwidth");
+        }
+        self.indent_string(width)
+    }
+
+    /// Get the indentation for a new line above the nth line (`O`). With `smartindent`, this is
+    /// the indentation following the line above, otherwise the indentation of the nth line.
+    pub fn auto_indent_above(&self, n: usize) -> String {
+        if n > 0 && self.bool_option("smartindent") {
+            self.auto_indent(n - 1)
+        } else if self.bool_option("autoindent") {
+            self.indent_string(self.indent_width(n))
+        } else {
+            String::new()
+        }
+    }
+
+    /// Dedent the nth line to the indentation of the line with the matching opening brace, after
+    /// `}` was typed as the first non-blank of it. If there is no matching brace, the line is
+    /// dedented by a level.
+    pub fn dedent_closing_brace(&mut self, n: usize) {
+        let width = match self.matching_bracket() {
+            Some((_, y)) if y < n => self.indent_width(y),
+            _ => self
+                .indent_width(n)
+                .saturating_sub(self.int_option("shiftwidth")),
+        };
+
+        let indent = self.indent_string(width);
+        let old_len = self.buffers.current_buffer().get_indent(n).len();
+        self.buffers.current_buffer_mut()[n].replace_range(..old_len, &indent);
+    }
+
+    /// Reindent the lines from `sy` to `ey` by their brace nesting: Every unclosed `{`, `(` or
+    /// `[` indents the following lines one `shiftwidth` further, and lines starting with closing
+    /// braces are dedented to the level of the matching line. The first line is indented
+    /// relative to the closest non-blank line above.
+    pub fn reindent_lines(&mut self, sy: usize, ey: usize) {
+        let sw = self.int_option("shiftwidth") as isize;
+
+        let mut width = (0..sy)
+            .rev()
+            .find(|&y| !self.buffers.current_buffer()[y].trim().is_empty())
+            .map_or(0, |y| {
+                let (lead, net) = brace_balance(&self.buffers.current_buffer()[y]);
+                self.indent_width(y) as isize + (net + lead as isize) * sw
+            });
+
+        for y in sy..ey + 1 {
+            let content = self.buffers.current_buffer()[y].trim().to_owned();
+            if content.is_empty() {
+                self.buffers.current_buffer_mut()[y] = content;
+                continue;
+            }
+
+            let (lead, net) = brace_balance(&content);
+            let indent = (width - lead as isize * sw).max(0);
+            width = indent + (net + lead as isize) * sw;
+
+            self.buffers.current_buffer_mut()[y] = self.indent_string(indent as usize) + &content;
+        }
+    }
+
+    /// Set the tab options of the current buffer to match its detected indentation style, if
+    /// `detectindent` is set.
+    pub fn apply_detected_indent(&mut self) {
+        if !self.bool_option("detectindent") {
+            return;
+        }
+
+        let lines = self.buffers.current_buffer().lines().map(String::as_str);
+        let opts = match detect_indent(lines) {
+            Some(IndentStyle::Tabs) => {
+                let tabstop = self.int_option("tabstop");
+                vec![
+                    ("expandtab", OptionValue::Bool(false)),
+                    ("shiftwidth", OptionValue::Int(tabstop)),
+                    ("softtabstop", OptionValue::Int(0)),
+                ]
+            }
+            Some(IndentStyle::Spaces(width)) => vec![
+                ("expandtab", OptionValue::Bool(true)),
+                ("shiftwidth", OptionValue::Int(width)),
+                ("softtabstop", OptionValue::Int(width)),
+            ],
+            None => return,
+        };
+
+        for (name, value) in opts {
+            // The detected widths are always valid values
+            let _ = self.set_option(name, value, SetTarget::Local);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(con: &str) -> Option<IndentStyle> {
+        detect_indent(con.lines())
+    }
+
+    #[test]
+    fn indent_width_is_the_most_common_step() {
+        let con = "fn a() {\n    if b {\n        c();\n    }\n}\n";
+        assert_eq!(detect(con), Some(IndentStyle::Spaces(4)));
+        let con = "a:\n  b:\n    c: 1\n  d: 2\n";
+        assert_eq!(detect(con), Some(IndentStyle::Spaces(2)));
+        // Ties go to the smaller width
+        assert_eq!(detect("a\n  b\nc\n    d\n"), Some(IndentStyle::Spaces(2)));
+    }
+
+    #[test]
+    fn tabs_win_if_more_lines_use_them() {
+        assert_eq!(detect("a\n\tb\n\tc\n    d\n"), Some(IndentStyle::Tabs));
+        assert_eq!(
+            detect("a\n\tb\n    c\n    d\n"),
+            Some(IndentStyle::Spaces(4))
+        );
+    }
+
+    #[test]
+    fn alignment_and_blank_lines_are_ignored() {
+        let con = "/**\n * Doc\n */\nfn a() {\n\n   \n    b();\n}\n";
+        assert_eq!(detect(con), Some(IndentStyle::Spaces(4)));
+        assert!(detect("a\n b\n\nc\n").is_none());
+        assert!(detect("").is_none());
+    }
+
+    #[test]
+    fn brace_balance_counts_leading_and_net_braces() {
+        assert_eq!(brace_balance("fn a() {"), (0, 1));
+        assert_eq!(brace_balance("    }"), (1, -1));
+        assert_eq!(brace_balance("}) {"), (2, -1));
+        assert_eq!(brace_balance("a[b(c)]"), (0, 0));
+    }
+
+    #[test]
+    fn brace_balance_skips_strings_chars_and_comments() {
+        assert_eq!(brace_balance(r#"let s = "{\"(";"#), (0, 0));
+        assert_eq!(brace_balance("let c = '{'; let d = '\\''; {"), (0, 1));
+        assert_eq!(brace_balance("let u = '\\u{7b}';"), (0, 0));
+        assert_eq!(brace_balance("fn a<'a>(x: &'a str) {"), (0, 1));
+        assert_eq!(brace_balance("} // {"), (1, -1));
+    }
+
+    #[test]
+    fn comments_start_outside_of_strings() {
+        let url = r#"let u = "http://x"; foo("#;
+        assert_eq!(strip_comment(url), url);
+        assert!(opens_block(url));
+        assert_eq!(brace_balance(url), (0, 1));
+        assert!(opens_block("foo( // bar"));
+        assert!(!opens_block("foo() // bar {"));
+        assert_eq!(strip_comment("a('/') // b"), "a('/') ");
+    }
+}
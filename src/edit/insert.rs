@@ -1,8 +1,12 @@
-use edit::buffer::TextBuffer;
+use caret::position::display_col;
+use edit::buffer::{byte_index, grapheme_count, grapheme_index, TextBuffer};
+use edit::indent::opens_block;
 use io::key::Key;
 use io::redraw::RedrawTask;
 use state::editor::Editor;
 
+use std::cmp::min;
+
 #[derive(Clone, PartialEq, Copy)]
 /// The type of the insert mode
 pub enum InsertMode {
@@ -23,46 +27,112 @@ impl Editor {
     /// Insert text under the current cursor.
     pub fn insert(&mut self, k: Key, InsertOptions { mode }: InsertOptions) {
         let (mut x, mut y) = self.pos();
+        self.push_change();
         self.buffers.current_buffer_info_mut().dirty = true;
         match (mode, k) {
             (InsertMode::Insert, Key::Char('\n')) => {
-                let first_part = self.buffers.current_buffer()[y][..x].to_owned();
-                let second_part = self.buffers.current_buffer()[y][x..].to_owned();
+                let line = &self.buffers.current_buffer()[y];
+                let b = byte_index(line, x);
+                let (first_part, second_part) = (line[..b].to_owned(), line[b..].to_owned());
 
+                // Like `foo(|)`: The cursor is right between an opening and a closing brace
+                let pair = opens_block(&first_part)
+                    && second_part.trim_start().starts_with(['}', ')', ']']);
                 self.buffers.current_buffer_mut()[y] = first_part;
 
-                let nl = if self.options.autoindent {
-                    self.buffers.current_buffer().get_indent(y).to_owned()
-                } else {
-                    String::new()
-                };
+                let nl = self.auto_indent(y);
                 let begin = nl.len();
 
-                self.buffers
-                    .current_buffer_mut()
-                    .insert_line(y + 1, nl + &second_part);
+                if pair && self.bool_option("smartindent") {
+                    // Splitting a pair of braces: The closing brace gets a line of its own, at
+                    // the indentation of the opening line
+                    let width = self.indent_width(y);
+                    let closing_line = self.indent_string(width) + second_part.trim_start();
+                    self.insert_line(y + 1, closing_line);
+                    self.insert_line(y + 1, nl);
+                } else {
+                    self.insert_line(y + 1, nl + &second_part);
+                }
 
                 self.redraw_task = RedrawTask::LinesAfter(y);
                 self.goto((begin, y + 1));
             }
-            (InsertMode::Insert, Key::Backspace) => self.backspace(),
-            (InsertMode::Insert, Key::Tab) => {
-                for i in 0..4 {
-                    self.buffers.current_buffer_mut()[y].insert(x + i, ' ');
+            (InsertMode::Insert, Key::Backspace) => {
+                let sts = self.int_option("softtabstop");
+                let line = &self.buffers.current_buffer()[y];
+                let b = byte_index(line, x);
+                let before = &line[..b];
+                if sts > 0 && before.ends_with(' ') {
+                    // Delete the spaces back to the previous soft tab stop
+                    let col = display_col(before, x, self.int_option("tabstop"));
+                    let spaces = before.chars().rev().take_while(|&c| c == ' ').count();
+                    let n = min(spaces, (col - 1) % sts + 1);
+                    self.buffers.current_buffer_mut()[y].drain(b - n..b);
+                    self.redraw_task = RedrawTask::Lines(y..y + 1);
+                    self.goto((x - n, y));
+                } else {
+                    self.backspace();
                 }
+            }
+            (InsertMode::Insert, Key::Tab) => {
+                let tabstop = self.int_option("tabstop");
+                let sts = self.int_option("softtabstop");
+                let line = self.buffers.current_buffer()[y].clone();
+                let col = display_col(&line, x, tabstop);
+                let mut b = byte_index(&line, x);
+
+                let tab = if !self.bool_option("expandtab") && (sts == 0 || sts == tabstop) {
+                    "\t".to_owned()
+                } else {
+                    // Fill up to the next (soft) tab stop. Without expandtab, the whitespace
+                    // before the cursor is redone with as many tabs as possible.
+                    let stop = if sts == 0 { tabstop } else { sts };
+                    let target = col + stop - col % stop;
+                    if self.bool_option("expandtab") {
+                        " ".repeat(target - col)
+                    } else {
+                        let ws = b - line[..b].trim_end_matches([' ', '\t']).len();
+                        let start = display_col(&line, x - ws, tabstop);
+                        self.buffers.current_buffer_mut()[y].drain(b - ws..b);
+                        x -= ws;
+                        b -= ws;
+                        let tabs = target / tabstop - start / tabstop;
+                        if tabs > 0 {
+                            "\t".repeat(tabs) + &" ".repeat(target % tabstop)
+                        } else {
+                            " ".repeat(target - start)
+                        }
+                    }
+                };
+
+                self.buffers.current_buffer_mut()[y].insert_str(b, &tab);
                 self.redraw_task = RedrawTask::Lines(y..y + 1);
-                let right = self.right(4, false);
-                self.goto(right);
+                self.goto((x + tab.len(), y));
             }
             (InsertMode::Insert, Key::Char(c)) => {
-                self.buffers.current_buffer_mut()[y].insert(x, c);
+                let b = byte_index(&self.buffers.current_buffer()[y], x);
+                self.buffers.current_buffer_mut()[y].insert(b, c);
+                // A combining mark joins the grapheme cluster before the cursor
+                let joined =
+                    grapheme_index(&self.buffers.current_buffer()[y], b + c.len_utf8()) == x;
+
+                let first = self.buffers.current_buffer()[y][..b].trim().is_empty();
+                if c == '}' && first && self.bool_option("smartindent") {
+                    // The cursor is on the brace, such that its match can be found
+                    let len = self.buffers.current_buffer()[y].len();
+                    self.dedent_closing_brace(y);
+                    x = x + self.buffers.current_buffer()[y].len() - len;
+                    self.goto((x, y));
+                }
 
                 self.redraw_task = RedrawTask::Lines(y..y + 1);
-                let right = self.right(1, false);
-                self.goto(right);
+                if !joined {
+                    let right = self.right(1, false);
+                    self.goto(right);
+                }
             }
             (InsertMode::Replace, Key::Char(c)) => {
-                if x == self.buffers.current_buffer()[y].len() {
+                if x == grapheme_count(&self.buffers.current_buffer()[y]) {
                     let next = self.next(1);
                     if let Some(p) = next {
                         self.goto(p);
@@ -72,14 +142,15 @@ impl Editor {
                 }
 
                 if self.buffers.current_buffer_mut().len() != y {
-                    if self.buffers.current_buffer()[y].len() == x {
+                    if grapheme_count(&self.buffers.current_buffer()[y]) == x {
                         let next = self.next(1);
                         if let Some(p) = next {
                             self.goto(p);
                         }
                     } else {
-                        self.buffers.current_buffer_mut()[y].remove(x);
-                        self.buffers.current_buffer_mut()[y].insert(x, c);
+                        let line = &self.buffers.current_buffer()[y];
+                        let range = byte_index(line, x)..byte_index(line, x + 1);
+                        self.buffers.current_buffer_mut()[y].replace_range(range, &c.to_string());
                     }
                 }
                 let next = self.next(1);
@@ -101,3 +172,36 @@ impl Editor {
         }
     }
 }
+
+#[cfg(all(test, not(feature = "orbital")))]
+mod tests {
+    use super::*;
+    use edit::buffer::SplitBuffer;
+    use state::options::{OptionValue, SetTarget};
+
+    fn enter(line: &str, x: usize) -> Vec<String> {
+        let mut editor = Editor::new();
+        let _ = editor.set_option("smartindent", OptionValue::Bool(true), SetTarget::Both);
+        *editor.buffers.current_buffer_mut() = SplitBuffer::from_str(line);
+        editor.goto((x, 0));
+        editor.insert(
+            Key::Char('\n'),
+            InsertOptions {
+                mode: InsertMode::Insert,
+            },
+        );
+        editor.buffers.current_buffer().lines().cloned().collect()
+    }
+
+    #[test]
+    fn enter_splits_an_empty_pair_of_braces() {
+        assert_eq!(enter("fn a() {}", 8), ["fn a() {", "    ", "}"]);
+        assert_eq!(enter("    [", 5), ["    [", "        "]);
+    }
+
+    #[test]
+    fn enter_keeps_closing_braces_after_content() {
+        assert_eq!(enter("foo(a, b)", 8), ["foo(a, b", ")"]);
+        assert_eq!(enter("[1, 2]", 5), ["[1, 2", "]"]);
+    }
+}
@@ -1,15 +1,19 @@
+use edit::buffer::byte_index;
 use state::editor::Editor;
 
 impl Editor {
     /// Invert n characters next to the cursor in the buffer.
     pub fn invert_chars(&mut self, n: usize) {
+        self.push_change();
         for _ in 0..n {
             let (x, y) = self.pos();
             let current = self.current();
 
             if let Some(cur) = current {
-                self.buffers.current_buffer_mut()[y].remove(x);
-                self.buffers.current_buffer_mut()[y].insert(x, invert(cur));
+                // Only the first char of the grapheme cluster is inverted
+                let b = byte_index(&self.buffers.current_buffer()[y], x);
+                let range = b..b + cur.len_utf8();
+                self.buffers.current_buffer_mut()[y].replace_range(range, &invert(cur).to_string());
             }
             if let Some(m) = self.next(1) {
                 self.goto(m);
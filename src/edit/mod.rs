@@ -2,9 +2,17 @@
 pub mod buffer;
 /// Delete text, defined by a motion.
 pub mod delete;
+/// Indentation.
+pub mod indent;
 /// Insertion of text.
 pub mod insert;
 /// "Invertion" of text.
 pub mod invert;
+/// Operators.
+///
+/// An operator is a command acting on the text given by a motion or text object, for example d.
+pub mod operator;
+/// Registers for yanked and deleted text.
+pub mod register;
 /// Selection through motions.
 pub mod selection;
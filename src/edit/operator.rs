@@ -0,0 +1,523 @@
+use caret::motion::{MotionType, Region};
+use edit::buffer::{byte_index, grapheme_count, TextBuffer};
+use edit::insert::{InsertMode, InsertOptions};
+use edit::register::Register;
+use io::key::Key;
+use io::parse::{Inst, Parameter};
+use io::redraw::RedrawTask;
+use state::editor::Editor;
+use state::mode::{Mode, PrimitiveMode};
+
+use std::cmp::min;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Copy, Clone, PartialEq)]
+/// An operator, i.e. a command acting on the text given by a following motion or text object.
+pub enum Operator {
+    /// Delete the text (d).
+    Delete,
+    /// Delete the text and go to insert mode (c).
+    Change,
+    /// Yank the text to the register (y).
+    Yank,
+    /// Indent the lines (>).
+    ShiftRight,
+    /// Dedent the lines (<).
+    ShiftLeft,
+    /// Make the text lowercase (gu).
+    Lowercase,
+    /// Make the text uppercase (gU).
+    Uppercase,
+    /// Toggle the case of the text (g~).
+    ToggleCase,
+    /// Reindent the lines (=).
+    Reindent,
+    /// Format the lines to the text width (gq).
+    Format,
+    /// Filter the lines through an external command (!).
+    Filter,
+}
+
+impl Operator {
+    /// Get the operator invoked by a given key. `g` is set if the key was preceded by g.
+    pub fn from_key(c: char, g: bool) -> Option<Operator> {
+        use self::Operator::*;
+
+        Some(match (g, c) {
+            (false, 'd') => Delete,
+            (false, 'c') => Change,
+            (false, 'y') => Yank,
+            (false, '>') => ShiftRight,
+            (false, '<') => ShiftLeft,
+            (false, '=') => Reindent,
+            (false, '!') => Filter,
+            (true, 'u') => Lowercase,
+            (true, 'U') => Uppercase,
+            (true, '~') => ToggleCase,
+            (true, 'q') => Format,
+            _ => return None,
+        })
+    }
+
+    /// The last key of the operator. Typing this key again (e.g. dd or gUU) applies the operator
+    /// on whole lines.
+    pub fn key(self) -> char {
+        use self::Operator::*;
+
+        match self {
+            Delete => 'd',
+            Change => 'c',
+            Yank => 'y',
+            ShiftRight => '>',
+            ShiftLeft => '<',
+            Lowercase => 'u',
+            Uppercase => 'U',
+            ToggleCase => '~',
+            Reindent => '=',
+            Format => 'q',
+            Filter => '!',
+        }
+    }
+}
+
+impl Editor {
+    /// Read a motion (or text object) and apply the operator on the text it covers. `n` is the
+    /// count given to the operator, which is multiplied with the count of the motion.
+    pub fn operator_pending(&mut self, op: Operator, n: usize) {
+        let Inst(para, cmd) = self.get_inst();
+        let para = match para {
+            Parameter::Null if n == 1 => Parameter::Null,
+            p => Parameter::Int(n * p.d()),
+        };
+
+        let region = match cmd.key {
+            // Doubled operator, i.e. work on the n next lines
+            Key::Char(c) if c == op.key() => {
+                let y = self.y();
+                let last = self.buffers.current_buffer().len() - 1;
+                Some(Region::new(
+                    (0, y),
+                    (0, min(y + para.d() - 1, last)),
+                    MotionType::Linewise,
+                ))
+            }
+            // Like in Vim, cw on a word changes to the end of the word
+            Key::Char('w')
+                if op == Operator::Change && self.current().is_some_and(|c| !c.is_whitespace()) =>
+            {
+                let pos = self.pos();
+                Some(Region::new(
+                    pos,
+                    self.next_word_end(para.d(), true),
+                    MotionType::Inclusive,
+                ))
+            }
+            _ => self.to_region(Inst(para, cmd)),
+        };
+
+        if let Some(region) = region {
+            self.apply_operator(op, region);
+        }
+    }
+
+    /// Apply an operator on a region.
+    pub fn apply_operator(&mut self, op: Operator, region: Region) {
+        use self::Operator::*;
+
+        if region.is_empty() && op != Change {
+            return;
+        }
+
+        let (sy, ey) = (region.start.1, region.end.1);
+
+        match op {
+            Yank => {
+                self.yank(&region);
+                self.goto(region.start);
+                // Like in Vim, only yanks spanning lines are reported
+                match region.kind {
+                    MotionType::Linewise => {
+                        self.status_bar.msg = format!("{} lines yanked", ey - sy + 1);
+                    }
+                    MotionType::Blockwise => {
+                        self.status_bar.msg = format!("block of {} lines yanked", ey - sy + 1);
+                    }
+                    _ => {}
+                }
+            }
+            Delete => {
+                self.yank(&region);
+                self.delete_region(&region);
+                let start = if region.kind == MotionType::Linewise {
+                    let y = self.bound_ver((0, sy)).1;
+                    (self.buffers.current_buffer().get_indent(y).len(), y)
+                } else {
+                    region.start
+                };
+                let bounded = self.bound(start, true);
+                self.goto(bounded);
+            }
+            Change => {
+                self.yank(&region);
+                if region.kind == MotionType::Linewise {
+                    // Keep one (indented) line to insert into
+                    let line = self.auto_indent_above(sy);
+                    let last = Region::new((0, sy + 1), (0, ey), MotionType::Linewise);
+                    if ey > sy {
+                        self.delete_region(&last);
+                    }
+                    let x = line.len();
+                    self.buffers.current_buffer_mut()[sy] = line;
+                    self.goto((x, sy));
+                } else {
+                    self.delete_region(&region);
+                    self.goto(region.start);
+                }
+                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Insert(InsertOptions {
+                    mode: InsertMode::Insert,
+                }));
+            }
+            ShiftRight | ShiftLeft => {
+                self.shift_lines(sy, ey, op == ShiftRight, 1);
+                let indent = self.buffers.current_buffer().get_indent(sy).len();
+                self.goto((indent, sy));
+            }
+            Lowercase => {
+                self.map_region(&region, |c| c.to_lowercase().next().unwrap_or(c));
+                self.goto(region.start);
+            }
+            Uppercase => {
+                self.map_region(&region, |c| c.to_uppercase().next().unwrap_or(c));
+                self.goto(region.start);
+            }
+            ToggleCase => {
+                self.map_region(&region, |c| {
+                    if c.is_lowercase() {
+                        c.to_uppercase().next().unwrap_or(c)
+                    } else {
+                        c.to_lowercase().next().unwrap_or(c)
+                    }
+                });
+                self.goto(region.start);
+            }
+            Reindent => {
+                self.reindent_lines(sy, ey);
+                let indent = self.buffers.current_buffer().get_indent(sy).len();
+                self.goto((indent, sy));
+            }
+            Format => {
+                let last = self.format_lines(sy, ey);
+                self.goto((0, last));
+            }
+            Filter => {
+                // The command is read from the prompt, see `PromptCommand::Filter`
+                self.filter_range = Some((sy, ey));
+                self.prompt_index = 0;
+                self.prompt[0] = "!".to_owned();
+                self.cursor_mut().mode = Mode::Primitive(PrimitiveMode::Prompt);
+                self.redraw_task = RedrawTask::StatusBar;
+                return;
+            }
+        }
+
+        if op != Yank {
+            self.push_change();
+            self.buffers.current_buffer_info_mut().dirty = true;
+        }
+        self.redraw_task = RedrawTask::LinesAfter(sy);
+        self.hint();
+    }
+
+    /// Get the text in a region, as lines.
+    pub fn region_text(&self, region: &Region) -> Vec<String> {
+        let buffer = self.buffers.current_buffer();
+        let ((sx, sy), (ex, ey)) = (region.start, region.end);
+
+        match region.kind {
+            MotionType::Linewise => (sy..ey + 1).map(|y| buffer[y].clone()).collect(),
+            MotionType::Blockwise => (sy..ey + 1)
+                .map(|y| {
+                    let line = &buffer[y];
+                    line[byte_index(line, sx)..byte_index(line, ex + 1)].to_owned()
+                })
+                .collect(),
+            MotionType::Exclusive | MotionType::Inclusive => {
+                let ex = if region.kind == MotionType::Inclusive {
+                    ex + 1
+                } else {
+                    ex
+                };
+
+                if sy == ey {
+                    let line = &buffer[sy];
+                    vec![line[byte_index(line, sx)..byte_index(line, ex)].to_owned()]
+                } else {
+                    let mut lines = Vec::new();
+                    lines.push(buffer[sy][byte_index(&buffer[sy], sx)..].to_owned());
+                    for y in sy + 1..ey {
+                        lines.push(buffer[y].clone());
+                    }
+                    lines.push(buffer[ey][..byte_index(&buffer[ey], ex)].to_owned());
+                    lines
+                }
+            }
+        }
+    }
+
+    /// Copy the text in a region to the register.
+    pub fn yank(&mut self, region: &Region) {
+        self.register = Some(Register {
+            lines: self.region_text(region),
+            kind: region.kind,
+        });
+    }
+
+    /// Delete the text in a region.
+    pub fn delete_region(&mut self, region: &Region) {
+        let ((sx, sy), (ex, ey)) = (region.start, region.end);
+
+        match region.kind {
+            MotionType::Linewise => {
+                for _ in sy..ey + 1 {
+                    if self.buffers.current_buffer().len() > 1 {
+                        self.remove_line(sy);
+                    } else {
+                        self.buffers.current_buffer_mut()[0] = String::new();
+                    }
+                }
+            }
+            MotionType::Blockwise => {
+                for y in sy..ey + 1 {
+                    let line = &mut self.buffers.current_buffer_mut()[y];
+                    let range = byte_index(line, sx)..byte_index(line, ex + 1);
+                    line.drain(range);
+                }
+            }
+            MotionType::Exclusive | MotionType::Inclusive => {
+                let ex = if region.kind == MotionType::Inclusive {
+                    ex + 1
+                } else {
+                    ex
+                };
+
+                if sy == ey {
+                    let line = &mut self.buffers.current_buffer_mut()[sy];
+                    let range = byte_index(line, sx)..byte_index(line, ex);
+                    line.drain(range);
+                } else {
+                    let tail = {
+                        let last = &self.buffers.current_buffer()[ey];
+                        last[byte_index(last, ex)..].to_owned()
+                    };
+                    {
+                        let first = &mut self.buffers.current_buffer_mut()[sy];
+                        let b = byte_index(first, sx);
+                        first.truncate(b);
+                        first.push_str(&tail);
+                    }
+                    for _ in sy + 1..ey + 1 {
+                        self.remove_line(sy + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replace every character in a region by the result of `f`.
+    fn map_region<F: Fn(char) -> char>(&mut self, region: &Region, f: F) {
+        let ((sx, sy), (ex, ey)) = (region.start, region.end);
+
+        for y in sy..ey + 1 {
+            let line = self.buffers.current_buffer()[y].clone();
+            let len = grapheme_count(&line);
+            let (a, b) = match region.kind {
+                MotionType::Linewise => (0, len),
+                MotionType::Blockwise => (min(sx, len), min(ex + 1, len)),
+                kind => {
+                    let end = if kind == MotionType::Inclusive {
+                        ex + 1
+                    } else {
+                        ex
+                    };
+                    (
+                        if y == sy { min(sx, len) } else { 0 },
+                        if y == ey { min(end, len) } else { len },
+                    )
+                }
+            };
+
+            let (a, b) = (byte_index(&line, a), byte_index(&line, b));
+            let mapped: String = line[a..b].chars().map(&f).collect();
+            self.buffers.current_buffer_mut()[y] = line[..a].to_owned() + &mapped + &line[b..];
+        }
+    }
+
+    /// Format the lines from `sy` to `ey`, such that no line is longer than `textwidth`.
+    /// Paragraphs (separated by blank lines) are kept apart. Returns the last formatted line.
+    pub fn format_lines(&mut self, sy: usize, ey: usize) -> usize {
+        let mut formatted = Vec::new();
+        let mut words: Vec<String> = Vec::new();
+        let mut indent = String::new();
+        let width = self.int_option("textwidth");
+
+        fn flush(words: &mut Vec<String>, indent: &str, width: usize, formatted: &mut Vec<String>) {
+            let mut line = String::new();
+            for word in words.drain(..) {
+                if !line.is_empty() && line.width() + 1 + word.width() > width {
+                    formatted.push(line);
+                    line = String::new();
+                }
+                if line.is_empty() {
+                    line.push_str(indent);
+                } else {
+                    line.push(' ');
+                }
+                line.push_str(&word);
+            }
+            if !line.is_empty() {
+                formatted.push(line);
+            }
+        }
+
+        for y in sy..ey + 1 {
+            let line = self.buffers.current_buffer()[y].clone();
+            if line.trim().is_empty() {
+                flush(&mut words, &indent, width, &mut formatted);
+                formatted.push(String::new());
+            } else {
+                if words.is_empty() {
+                    indent = self.buffers.current_buffer().get_indent(y).to_owned();
+                }
+                words.extend(line.split_whitespace().map(|s| s.to_owned()));
+            }
+        }
+        flush(&mut words, &indent, width, &mut formatted);
+
+        for _ in sy..ey + 1 {
+            self.remove_line(sy);
+        }
+        let count = formatted.len();
+        for (i, line) in formatted.into_iter().enumerate() {
+            self.insert_line(sy + i, line);
+        }
+        if self.buffers.current_buffer().len() == 0 {
+            self.insert_line(0, String::new());
+        }
+
+        sy + count.max(1) - 1
+    }
+
+    /// Filter the lines from `sy` to `ey` through an external shell command, replacing them with
+    /// its output.
+    pub fn filter_lines(&mut self, sy: usize, ey: usize, command: &str) -> Result<(), String> {
+        let input: String = (sy..ey + 1)
+            .map(|y| self.buffers.current_buffer()[y].clone() + "\n")
+            .collect();
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not run {}: {}", command, e))?;
+
+        // Write from another thread while reading the output, as the command may block on a full
+        // stdout pipe before it has consumed all of its input
+        let writer = child
+            .stdin
+            .take()
+            .map(|mut stdin| thread::spawn(move || stdin.write_all(input.as_bytes())));
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Could not read from {}: {}", command, e))?;
+        if let Some(writer) = writer {
+            match writer.join() {
+                Ok(Ok(())) => (),
+                // The command may exit without reading all of its input (like `head`)
+                Ok(Err(ref e)) if e.kind() == ErrorKind::BrokenPipe => (),
+                Ok(Err(e)) => return Err(format!("Could not write to {}: {}", command, e)),
+                Err(_) => return Err(format!("Could not write to {}", command)),
+            }
+        }
+
+        for _ in sy..ey + 1 {
+            self.remove_line(sy);
+        }
+        let output = String::from_utf8_lossy(&output.stdout);
+        let mut count = 0;
+        for (i, line) in output.lines().enumerate() {
+            self.insert_line(sy + i, line.to_owned());
+            count += 1;
+        }
+        if self.buffers.current_buffer().len() == 0 {
+            self.insert_line(0, String::new());
+        }
+
+        self.push_change();
+        self.buffers.current_buffer_info_mut().dirty = true;
+        self.goto((0, sy));
+        self.redraw_task = RedrawTask::Full;
+        self.hint();
+
+        self.status_bar.msg = format!("{} lines filtered", count);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "orbital")))]
+mod tests {
+    use super::*;
+    use edit::buffer::SplitBuffer;
+    use io::key::parse_keys;
+
+    /// Type `keys` in normal mode on `text`, with the cursor at `pos`.
+    fn run(text: &str, pos: (usize, usize), keys: &str) -> (Vec<String>, (usize, usize)) {
+        let mut editor = Editor::new();
+        *editor.buffers.current_buffer_mut() = SplitBuffer::from_str(text);
+        editor.goto(pos);
+        editor.pending_keys.extend(parse_keys(keys).unwrap());
+        while !editor.pending_keys.is_empty() {
+            let inst = editor.get_inst();
+            editor.exec(inst);
+        }
+        let lines = editor.buffers.current_buffer().lines().cloned().collect();
+        (lines, editor.pos())
+    }
+
+    #[test]
+    fn operators_take_motions_and_counts() {
+        assert_eq!(run("one two three", (0, 0), "dw").0, ["two three"]);
+        assert_eq!(run("one two three", (0, 0), "d2w").0, ["three"]);
+        assert_eq!(run("one two three", (0, 0), "2dw").0, ["three"]);
+        assert_eq!(run("one two three", (4, 0), "d$").0, ["one "]);
+        assert_eq!(run("one two", (0, 0), "gUw").0, ["ONE two"]);
+        assert_eq!(run("One", (0, 0), "g~l").0, ["one"]);
+    }
+
+    #[test]
+    fn doubled_operators_work_on_lines() {
+        assert_eq!(
+            run("a\nb\nc", (0, 0), "2dd"),
+            (vec!["c".to_owned()], (0, 0))
+        );
+        assert_eq!(run("a\nb\nc", (0, 1), "5dd").0, ["a"]);
+        assert_eq!(run("a\nb", (0, 0), "gUU").0, ["A", "b"]);
+        assert_eq!(run("a", (0, 0), ">>").0, ["    a"]);
+    }
+
+    #[test]
+    fn change_word_keeps_the_following_space() {
+        assert_eq!(run("one two", (0, 0), "cwx").0, ["x two"]);
+    }
+
+    #[test]
+    fn yanked_lines_are_put() {
+        assert_eq!(run("a\nb", (0, 0), "yyp").0, ["a", "a", "b"]);
+        assert_eq!(run("a\nb", (0, 1), "ykP").0, ["a", "b", "a", "b"]);
+        assert_eq!(run("one two", (0, 0), "dwP").0, ["one two"]);
+    }
+}
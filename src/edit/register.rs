@@ -0,0 +1,92 @@
+use caret::motion::MotionType;
+use edit::buffer::{byte_index, grapheme_count, TextBuffer};
+use io::redraw::RedrawTask;
+use state::editor::Editor;
+
+#[derive(Clone)]
+/// A register, i.e. a piece of yanked or deleted text.
+pub struct Register {
+    /// The lines of the text.
+    pub lines: Vec<String>,
+    /// How the text was selected, which defines how it is put back.
+    pub kind: MotionType,
+}
+
+impl Editor {
+    /// Put the text of the register n times after (`after` set) or before the cursor.
+    pub fn put(&mut self, after: bool, n: usize) {
+        let Register { lines, kind } = match self.register.clone() {
+            Some(r) => r,
+            None => {
+                self.status_bar.msg = "Nothing in register".to_owned();
+                return;
+            }
+        };
+
+        self.push_change();
+        self.buffers.current_buffer_info_mut().dirty = true;
+
+        let (x, y) = self.pos();
+        match kind {
+            MotionType::Linewise => {
+                let at = if after { y + 1 } else { y };
+                for _ in 0..n {
+                    for line in lines.iter().rev() {
+                        self.insert_line(at, line.clone());
+                    }
+                }
+                let indent = self.buffers.current_buffer().get_indent(at).len();
+                self.goto((indent, at));
+            }
+            MotionType::Blockwise => {
+                let len = grapheme_count(&self.buffers.current_buffer()[y]);
+                let col = if after && len > 0 { x + 1 } else { x };
+                for (i, part) in lines.iter().enumerate() {
+                    let ry = y + i;
+                    if ry >= self.buffers.current_buffer().len() {
+                        let end = self.buffers.current_buffer().len();
+                        self.insert_line(end, String::new());
+                    }
+
+                    let line = &mut self.buffers.current_buffer_mut()[ry];
+                    let len = grapheme_count(line);
+                    if len < col {
+                        line.push_str(&" ".repeat(col - len));
+                    }
+                    let b = byte_index(line, col);
+                    line.insert_str(b, &part.repeat(n));
+                }
+                self.goto((col, y));
+            }
+            MotionType::Exclusive | MotionType::Inclusive => {
+                let len = grapheme_count(&self.buffers.current_buffer()[y]);
+                let col = if after { (x + 1).min(len) } else { x };
+
+                let text = lines.join("\n").repeat(n);
+                let mut new_lines: Vec<String> = text.split('\n').map(|s| s.to_owned()).collect();
+
+                let b = byte_index(&self.buffers.current_buffer()[y], col);
+                let tail = self.buffers.current_buffer_mut()[y].split_off(b);
+                let last = new_lines.len() - 1;
+                let last_len = grapheme_count(&new_lines[last]);
+                new_lines[last].push_str(&tail);
+
+                let first = new_lines.remove(0);
+                self.buffers.current_buffer_mut()[y].push_str(&first);
+                for (i, line) in new_lines.into_iter().enumerate() {
+                    self.insert_line(y + 1 + i, line);
+                }
+
+                // The cursor ends on the last character of the text, if it is on one line
+                if last == 0 && last_len > 0 {
+                    self.goto((col + last_len - 1, y));
+                } else {
+                    self.goto((col, y));
+                }
+            }
+        }
+
+        self.redraw_task = RedrawTask::LinesAfter(y);
+        self.hint();
+    }
+}
@@ -1,4 +1,4 @@
-use edit::buffer::TextBuffer;
+use edit::buffer::{byte_index, TextBuffer};
 use state::editor::Editor;
 
 impl Editor {
@@ -7,6 +7,7 @@ impl Editor {
     /// defines a position on the same line, only the characters from the current position to the
     /// motion's position are removed.
     pub fn remove_rb<'a>(&mut self, (x, y): (isize, isize)) {
+        self.push_change();
         if y == (self.y() as isize) {
             let (x, y) = self.bound((x as usize, y as usize), false);
             // Single line mode
@@ -15,7 +16,9 @@ impl Editor {
             } else {
                 (self.x(), x)
             };
-            for _ in self.buffers.current_buffer_mut()[y].drain(a..b) {}
+            let line = &self.buffers.current_buffer()[y];
+            let range = byte_index(line, a)..byte_index(line, b);
+            for _ in self.buffers.current_buffer_mut()[y].drain(range) {}
         } else {
             let (_, y) = self.bound((x as usize, y as usize), true);
             // Full line mode
@@ -28,7 +31,7 @@ impl Editor {
             // TODO: Make this more idiomatic (drain)
             for _ in a..(b + 1) {
                 if self.buffers.current_buffer().len() > 1 {
-                    self.buffers.current_buffer_mut().remove_line(a);
+                    self.remove_line(a);
                 } else {
                     self.buffers.current_buffer_mut()[0] = String::new();
                 }
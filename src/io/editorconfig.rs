@@ -0,0 +1,461 @@
+use state::editor::Editor;
+use state::options::{OptionValue, SetTarget};
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// A section of an `.editorconfig` file.
+pub struct Section {
+    /// The glob matching the files the section applies to.
+    pub glob: String,
+    /// The properties of the section, as lowercase key-value pairs.
+    pub properties: Vec<(String, String)>,
+}
+
+/// A parsed `.editorconfig` file.
+pub struct EditorConfig {
+    /// The directory of the file. Globs are relative to this.
+    pub dir: PathBuf,
+    /// Whether the file is the top-most one (`root = true`).
+    pub root: bool,
+    /// The sections of the file, in order.
+    pub sections: Vec<Section>,
+}
+
+impl EditorConfig {
+    /// Parse the content of an `.editorconfig` file in a given directory. Invalid lines are
+    /// ignored.
+    pub fn parse(con: &str, dir: PathBuf) -> EditorConfig {
+        let mut config = EditorConfig {
+            dir,
+            root: false,
+            sections: Vec::new(),
+        };
+
+        for line in con.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                config.sections.push(Section {
+                    glob: line[1..line.len() - 1].to_owned(),
+                    properties: Vec::new(),
+                });
+            } else if let Some(eq) = line.find('=') {
+                let key = line[..eq].trim().to_lowercase();
+                let value = line[eq + 1..].trim().to_lowercase();
+                match config.sections.last_mut() {
+                    Some(section) => section.properties.push((key, value)),
+                    // Properties before the first section are the preamble
+                    None if key == "root" => config.root = value == "true",
+                    None => {}
+                }
+            }
+        }
+
+        config
+    }
+
+    /// Check if a section glob of this file matches a given (absolute) path.
+    pub fn matches(&self, glob: &str, path: &Path) -> bool {
+        let rel = match path.strip_prefix(&self.dir) {
+            Ok(rel) => rel.to_string_lossy().replace('\\', "/"),
+            Err(_) => return false,
+        };
+
+        // Globs without a slash match the file name in any directory
+        let glob = if glob.contains('/') {
+            format!("/{}", glob.trim_start_matches('/'))
+        } else {
+            format!("**/{}", glob)
+        };
+
+        let glob: Vec<char> = glob.chars().collect();
+        let path: Vec<char> = format!("/{}", rel).chars().collect();
+        glob_match(&parse_glob(&glob), &path)
+    }
+}
+
+/// A token of a glob.
+enum Token {
+    /// A literal char.
+    Char(char),
+    /// `?`, any char except `/`.
+    Any,
+    /// `*`, any string without `/`.
+    Star,
+    /// `**`, any string.
+    DoubleStar,
+    /// `[...]` (or `[!...]`, if negated), any char in the ranges.
+    Class(bool, Vec<(char, char)>),
+    /// `{s1,s2,...}`, any of the alternatives.
+    Alt(Vec<Vec<Token>>),
+    /// `{n1..n2}`, any integer in the range.
+    Range(i64, i64),
+}
+
+/// Find the index of the `}` closing the `{` at index `open`.
+fn closing_brace(glob: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    while i < glob.len() {
+        match glob[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Split a glob at the commas, which are not nested in braces.
+fn split_alternatives(glob: &[char]) -> Vec<&[char]> {
+    let mut alts = Vec::new();
+    let (mut depth, mut start, mut i) = (0, 0, 0);
+    while i < glob.len() {
+        match glob[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alts.push(&glob[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    alts.push(&glob[start..]);
+    alts
+}
+
+/// Parse a numeric range (`n1..n2`).
+fn parse_range(s: &str) -> Option<(i64, i64)> {
+    let dots = s.find("..")?;
+    Some((s[..dots].parse().ok()?, s[dots + 2..].parse().ok()?))
+}
+
+/// Parse a glob into tokens.
+fn parse_glob(glob: &[char]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < glob.len() {
+        match glob[i] {
+            '\\' if i + 1 < glob.len() => {
+                i += 1;
+                tokens.push(Token::Char(glob[i]));
+            }
+            '?' => tokens.push(Token::Any),
+            '*' if glob.get(i + 1) == Some(&'*') => {
+                i += 1;
+                tokens.push(Token::DoubleStar);
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => match glob[i..].iter().position(|&c| c == ']') {
+                Some(len) if len > 1 => {
+                    let inner = &glob[i + 1..i + len];
+                    let (negated, inner) = match inner.first() {
+                        Some('!') | Some('^') => (true, &inner[1..]),
+                        _ => (false, inner),
+                    };
+
+                    let mut ranges = Vec::new();
+                    let mut j = 0;
+                    while j < inner.len() {
+                        if j + 2 < inner.len() && inner[j + 1] == '-' {
+                            ranges.push((inner[j], inner[j + 2]));
+                            j += 3;
+                        } else {
+                            ranges.push((inner[j], inner[j]));
+                            j += 1;
+                        }
+                    }
+
+                    tokens.push(Token::Class(negated, ranges));
+                    i += len;
+                }
+                _ => tokens.push(Token::Char('[')),
+            },
+            '{' => match closing_brace(glob, i) {
+                Some(close) => {
+                    let inner = &glob[i + 1..close];
+                    let inner_str: String = inner.iter().collect();
+                    let alts = split_alternatives(inner);
+
+                    if let Some((from, to)) = parse_range(&inner_str) {
+                        tokens.push(Token::Range(from, to));
+                    } else if alts.len() > 1 {
+                        tokens.push(Token::Alt(alts.into_iter().map(parse_glob).collect()));
+                    } else {
+                        // A single alternative is literal
+                        tokens.push(Token::Char('{'));
+                        tokens.extend(parse_glob(inner));
+                        tokens.push(Token::Char('}'));
+                    }
+                    i = close;
+                }
+                None => tokens.push(Token::Char('{')),
+            },
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+
+    tokens
+}
+
+/// Match a path against glob tokens.
+fn glob_match(tokens: &[Token], path: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return path.is_empty(),
+    };
+
+    match *token {
+        Token::Char(c) => path.first() == Some(&c) && glob_match(rest, &path[1..]),
+        Token::Any => path.first().is_some_and(|&c| c != '/') && glob_match(rest, &path[1..]),
+        Token::Star => {
+            let max = path.iter().position(|&c| c == '/').unwrap_or(path.len());
+            (0..max + 1).any(|i| glob_match(rest, &path[i..]))
+        }
+        Token::DoubleStar => (0..path.len() + 1).any(|i| glob_match(rest, &path[i..])),
+        Token::Class(negated, ref ranges) => match path.first() {
+            Some(&c) if c != '/' => {
+                let found = ranges.iter().any(|&(a, b)| a <= c && c <= b);
+                found != negated && glob_match(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Alt(ref alts) => alts.iter().any(|alt| {
+            (0..path.len() + 1).any(|i| glob_match(alt, &path[..i]) && glob_match(rest, &path[i..]))
+        }),
+        Token::Range(from, to) => {
+            let sign = usize::from(path.first() == Some(&'-'));
+            let digits = path[sign..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            (sign + 1..sign + digits + 1).any(|i| {
+                let n: String = path[..i].iter().collect();
+                n.parse()
+                    .is_ok_and(|n: i64| from <= n && n <= to && glob_match(rest, &path[i..]))
+            })
+        }
+    }
+}
+
+/// Get the EditorConfig properties applying to a file, by reading the `.editorconfig` files from
+/// its directory up to the root (or a file with `root = true`). Nearer files and later sections
+/// take precedence.
+pub fn properties(path: &str) -> Vec<(String, String)> {
+    let path = match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => PathBuf::from(path),
+    };
+
+    let mut configs = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        let mut con = String::new();
+        if let Ok(mut file) = File::open(dir.join(".editorconfig")) {
+            if file.read_to_string(&mut con).is_ok() {
+                let config = EditorConfig::parse(&con, dir.to_path_buf());
+                let root = config.root;
+                configs.push(config);
+                if root {
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut props: Vec<(String, String)> = Vec::new();
+    for config in configs.iter().rev() {
+        for section in config.sections.iter() {
+            if !config.matches(&section.glob, &path) {
+                continue;
+            }
+            for (key, value) in section.properties.iter() {
+                match props.iter_mut().find(|(k, _)| k == key) {
+                    Some(prop) => prop.1 = value.clone(),
+                    None => props.push((key.clone(), value.clone())),
+                }
+            }
+        }
+    }
+
+    props
+}
+
+impl Editor {
+    /// Apply the EditorConfig properties of a file to the options of the current buffer, if
+    /// `editorconfig` is set. Unknown properties and invalid values are ignored.
+    pub fn apply_editorconfig(&mut self, path: &str) {
+        if !self.bool_option("editorconfig") {
+            return;
+        }
+
+        let props = properties(path);
+        let get = |key: &str| {
+            props
+                .iter()
+                .find(|(k, v)| k == key && v != "unset")
+                .map(|(_, v)| v.as_str())
+        };
+        let mut opts = Vec::new();
+
+        match get("indent_style") {
+            Some("tab") => opts.push(("expandtab", OptionValue::Bool(false))),
+            Some("space") => opts.push(("expandtab", OptionValue::Bool(true))),
+            _ => {}
+        }
+
+        // The tab width defaults to the indentation size, and vice versa for `indent_size = tab`
+        let indent_size = get("indent_size").and_then(|v| v.parse().ok());
+        let tab_width = get("tab_width").and_then(|v| v.parse().ok());
+        if let Some(width) = tab_width.or(indent_size) {
+            opts.push(("tabstop", OptionValue::Int(width)));
+        }
+        match (indent_size, get("indent_size")) {
+            (Some(size), _) => opts.push(("shiftwidth", OptionValue::Int(size))),
+            (None, Some("tab")) => {
+                let width = tab_width.unwrap_or_else(|| self.int_option("tabstop"));
+                opts.push(("shiftwidth", OptionValue::Int(width)));
+            }
+            _ => {}
+        }
+
+        match get("end_of_line") {
+            Some("lf") => opts.push(("fileformat", OptionValue::Str("unix".to_owned()))),
+            Some("crlf") => opts.push(("fileformat", OptionValue::Str("dos".to_owned()))),
+            Some("cr") => opts.push(("fileformat", OptionValue::Str("mac".to_owned()))),
+            _ => {}
+        }
+
+        if let Some(charset) = get("charset") {
+            opts.push(("fileencoding", OptionValue::Str(charset.to_owned())));
+        }
+
+        match get("trim_trailing_whitespace") {
+            Some("true") => opts.push(("trimwhitespace", OptionValue::Bool(true))),
+            Some("false") => opts.push(("trimwhitespace", OptionValue::Bool(false))),
+            _ => {}
+        }
+        // `false` only means not to add a final newline, so a detected one is kept
+        if get("insert_final_newline") == Some("true") {
+            opts.push(("endofline", OptionValue::Bool(true)));
+        }
+
+        let indent_changed = opts
+            .iter()
+            .any(|&(name, _)| name == "expandtab" || name == "shiftwidth");
+
+        for (name, value) in opts {
+            let _ = self.set_option(name, value, SetTarget::Local);
+        }
+
+        // Make tab and backspace in insert mode follow the indentation
+        if indent_changed {
+            let sts = if self.bool_option("expandtab") {
+                self.int_option("shiftwidth")
+            } else {
+                0
+            };
+            let _ = self.set_option("softtabstop", OptionValue::Int(sts), SetTarget::Local);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        let config = EditorConfig::parse("", PathBuf::from("/p"));
+        config.matches(glob, &Path::new("/p").join(path))
+    }
+
+    #[test]
+    fn glob_without_slash_matches_in_any_directory() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*.rs", "src/io/key.rs"));
+        assert!(!matches("*.rs", "main.rs.bak"));
+        assert!(matches("Makefile", "sub/Makefile"));
+    }
+
+    #[test]
+    fn glob_with_slash_is_relative_to_the_file() {
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/io/key.rs"));
+        assert!(!matches("src/*.rs", "lib/src/main.rs"));
+        assert!(matches("/src/*.rs", "src/main.rs"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("src/**.rs", "src/io/key.rs"));
+        assert!(matches("src/**/key.rs", "src/io/key.rs"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(!matches("a/**/b", "c/x/b"));
+    }
+
+    #[test]
+    fn brace_sets_match_any_alternative() {
+        assert!(matches("*.{rs,toml}", "Cargo.toml"));
+        assert!(matches("*.{rs,toml}", "src/main.rs"));
+        assert!(!matches("*.{rs,toml}", "README.md"));
+        assert!(matches("{src,tests/{a,b}}/*.rs", "tests/b/x.rs"));
+        assert!(!matches("{src,tests/{a,b}}/*.rs", "tests/c/x.rs"));
+        // A single alternative is literal
+        assert!(matches("{a}.txt", "{a}.txt"));
+        assert!(!matches("{a}.txt", "a.txt"));
+    }
+
+    #[test]
+    fn numeric_ranges_match_integers() {
+        assert!(matches("file{1..3}.txt", "file1.txt"));
+        assert!(matches("file{1..3}.txt", "file3.txt"));
+        assert!(!matches("file{1..3}.txt", "file4.txt"));
+        assert!(!matches("file{1..3}.txt", "file.txt"));
+        assert!(matches("file{1..30}.txt", "file12.txt"));
+        assert!(matches("t{-5..5}", "t-3"));
+        assert!(!matches("t{-5..5}", "t-6"));
+    }
+
+    #[test]
+    fn classes_and_single_chars() {
+        assert!(matches("[ab].c", "a.c"));
+        assert!(!matches("[ab].c", "c.c"));
+        assert!(matches("[!ab].c", "c.c"));
+        assert!(matches("[a-c]?.c", "bx.c"));
+        assert!(!matches("?.c", "/.c"));
+        assert!(matches("\\*.c", "*.c"));
+        assert!(!matches("\\*.c", "a.c"));
+    }
+
+    #[test]
+    fn sections_and_preamble_are_parsed() {
+        let config = EditorConfig::parse(
+            "root = true\n# comment\n[*]\nIndent_Style = Tab\n; other\n[*.md]\nx=1",
+            PathBuf::from("/p"),
+        );
+        assert!(config.root);
+        assert_eq!(config.sections.len(), 2);
+        assert_eq!(config.sections[0].glob, "*");
+        assert_eq!(
+            config.sections[0].properties,
+            vec![("indent_style".to_owned(), "tab".to_owned())]
+        );
+    }
+}
@@ -1,7 +1,10 @@
 use edit::buffer::{SplitBuffer, TextBuffer};
+use io::redraw::RedrawTask;
 use state::editor::{Buffer, Editor};
+use state::options::{OptionValue, SetTarget};
 use std::fs::File;
 use std::io::{Read, Write};
+use std::iter;
 
 /// The status of a file IO operation.
 pub enum FileStatus {
@@ -13,12 +16,82 @@ pub enum FileStatus {
     Other,
 }
 
+/// Decode the content of a file, returning the text and the detected encoding. Files starting
+/// with a byte order mark are UTF-8 or UTF-16, and files which are not valid UTF-8 are read as
+/// Latin-1.
+fn decode(bytes: &[u8]) -> (String, &'static str) {
+    let utf16 = |bytes: &[u8], big_endian: bool| {
+        let units: Vec<u16> = bytes
+            .chunks(2)
+            .map(|c| {
+                let (a, b) = (c[0] as u16, *c.get(1).unwrap_or(&0) as u16);
+                if big_endian {
+                    a << 8 | b
+                } else {
+                    b << 8 | a
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        (String::from_utf8_lossy(rest).into_owned(), "utf-8-bom")
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        (utf16(rest, true), "utf-16be")
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        (utf16(rest, false), "utf-16le")
+    } else {
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(con) => (con, "utf-8"),
+            Err(_) => (bytes.iter().map(|&b| b as char).collect(), "latin1"),
+        }
+    }
+}
+
+/// Encode text in a given encoding (one of `FILE_ENCODINGS`). Chars which can't be encoded in
+/// Latin-1 are replaced by `?`.
+fn encode(con: &str, encoding: &str) -> Vec<u8> {
+    match encoding {
+        "utf-8-bom" => [0xEF, 0xBB, 0xBF]
+            .iter()
+            .chain(con.as_bytes())
+            .cloned()
+            .collect(),
+        "latin1" => con
+            .chars()
+            .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+            .collect(),
+        "utf-16be" => iter::once(0xFEFF)
+            .chain(con.encode_utf16())
+            .flat_map(|u: u16| u.to_be_bytes())
+            .collect(),
+        "utf-16le" => iter::once(0xFEFF)
+            .chain(con.encode_utf16())
+            .flat_map(|u: u16| u.to_le_bytes())
+            .collect(),
+        _ => con.as_bytes().to_vec(),
+    }
+}
+
 impl Editor {
-    /// Open a file.
+    /// Open a file. The encoding, line endings and final line ending of the file are kept in
+    /// the buffer-local `fileencoding`, `fileformat` and `endofline` options.
     pub fn open(&mut self, path: &str) -> FileStatus {
-        if let Some(mut file) = File::open(path).ok() {
-            let mut con = String::new();
-            let _ = file.read_to_string(&mut con);
+        if let Ok(mut file) = File::open(path) {
+            let mut bytes = Vec::new();
+            let _ = file.read_to_end(&mut bytes);
+
+            let (mut con, encoding) = decode(&bytes);
+            let format = if con.contains("\r\n") {
+                "dos"
+            } else if con.contains('\r') && !con.contains('\n') {
+                con = con.replace('\r', "\n");
+                "mac"
+            } else {
+                "unix"
+            };
+            let eol = con.is_empty() || con.ends_with('\n');
 
             if con.is_empty() {
                 con.push('\n');
@@ -29,6 +102,20 @@ impl Editor {
 
             let new_buffer_index = self.buffers.new_buffer(new_buffer);
             self.buffers.switch_to(new_buffer_index);
+
+            let local = [
+                ("fileencoding", OptionValue::Str(encoding.to_owned())),
+                ("fileformat", OptionValue::Str(format.to_owned())),
+                ("endofline", OptionValue::Bool(eol)),
+            ];
+            for (name, value) in local.iter() {
+                let _ = self.set_option(name, value.clone(), SetTarget::Local);
+            }
+
+            self.detect_filetype(path);
+            self.apply_detected_indent();
+            self.apply_editorconfig(path);
+            self.apply_modelines();
             self.hint();
             FileStatus::Ok
         } else {
@@ -39,14 +126,32 @@ impl Editor {
     /// Write the file.
     pub fn write<'a>(&'a mut self, path: &'a str) -> FileStatus {
         self.buffers.current_buffer_info_mut().title = Some(path.into());
-        if path == "" {
+        if path.is_empty() {
             return FileStatus::Other;
         }
-        if let Some(mut file) = File::create(path).ok() {
-            if file
-                .write(self.buffers.current_buffer().to_string().as_bytes())
-                .is_ok()
-            {
+        if self.bool_option("trimwhitespace") {
+            self.trim_trailing_whitespace();
+        }
+
+        let eol = match self.str_option("fileformat") {
+            "dos" => "\r\n",
+            "mac" => "\r",
+            _ => "\n",
+        };
+        let mut con = self
+            .buffers
+            .current_buffer()
+            .lines()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(eol);
+        if self.bool_option("endofline") {
+            con.push_str(eol);
+        }
+        let bytes = encode(&con, self.str_option("fileencoding"));
+
+        if let Ok(mut file) = File::create(path) {
+            if file.write_all(&bytes).is_ok() {
                 FileStatus::Ok
             } else {
                 FileStatus::Other
@@ -55,4 +160,13 @@ impl Editor {
             FileStatus::NotFound
         }
     }
+
+    /// Remove the trailing whitespace of every line in the current buffer.
+    pub fn trim_trailing_whitespace(&mut self) {
+        for y in 0..self.buffers.current_buffer().len() {
+            let len = self.buffers.current_buffer()[y].trim_end().len();
+            self.buffers.current_buffer_mut()[y].truncate(len);
+        }
+        self.redraw_task = RedrawTask::Full;
+    }
 }
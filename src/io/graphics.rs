@@ -1,7 +1,15 @@
 #[cfg(feature = "orbital")]
-use edit::buffer::TextBuffer;
+use caret::motion::MotionType;
 #[cfg(feature = "orbital")]
-use io::redraw::RedrawTask;
+use caret::position::{display_width, grapheme_width};
+#[cfg(feature = "orbital")]
+use edit::buffer::{grapheme_count, grapheme_index};
+#[cfg(feature = "orbital")]
+use io::highlight::Highlight;
+#[cfg(feature = "orbital")]
+use io::redraw::{RedrawTask, ScreenParams};
+#[cfg(feature = "orbital")]
+use io::theme::{Group, Rgb};
 use state::editor::Editor;
 #[cfg(feature = "orbital")]
 use state::mode::{Mode, PrimitiveMode};
@@ -9,229 +17,388 @@ use state::mode::{Mode, PrimitiveMode};
 #[cfg(feature = "orbital")]
 use orbclient::{Color, Renderer};
 
+#[cfg(feature = "orbital")]
+use std::cmp::{max, min};
+#[cfg(feature = "orbital")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "orbital")]
+use std::hash::{Hash, Hasher};
 use std::iter;
+#[cfg(feature = "orbital")]
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Convert a theme color to a window color.
+#[cfg(feature = "orbital")]
+fn color((r, g, b): Rgb) -> Color {
+    Color::rgb(r, g, b)
+}
+
+/// A drawn char, with its foreground and (if not the one of the row) background color.
+#[cfg(feature = "orbital")]
+type Cell = (char, Rgb, Option<Rgb>);
 
 #[cfg(feature = "orbital")]
 impl Editor {
-    /// Redraw the window
+    /// Redraw the window. Only the screen rows, which may have changed since the last frame (by
+    /// the redraw task, the lines changed in the buffer, the cursors and the selection), are
+    /// considered, and of those only the ones, whose content actually differs, are repainted.
     pub fn redraw(&mut self) {
         let w = self.window.width() as usize;
         let h = self.window.height() as usize;
 
         let vert_offset: usize = 0;
+        let horz_offset = self.gutter_width();
 
-        let horz_offset: usize = if self.options.line_numbers {
-            let len = self.buffers.current_buffer_info().raw_buffer.len();
-            let mut ret: usize = 3;
-            while len >= 10usize.pow((ret - 1) as u32) {
-                ret += 1;
-            }
-            ret
+        self.update_layout();
+        self.scroll_to_cursor();
+
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+        let tabstop = self.int_option("tabstop");
+
+        let search = if self.bool_option("hlsearch") {
+            self.last_search.clone().filter(|p| !p.is_empty())
         } else {
-            0
+            None
         };
 
-        let max_vert_chars = h / self.char_height - 2 - vert_offset;
-        let max_horz_chars = w / self.char_width - horz_offset;
+        let params = ScreenParams {
+            size: (w, h),
+            buffer: self.buffers.current_buffer_index(),
+            scroll_y,
+            scroll_x: self.buffers.current_buffer_info().scroll_x,
+            wrap: (
+                self.bool_option("wrap"),
+                self.bool_option("linebreak"),
+                self.str_option("showbreak").to_owned(),
+            ),
+            gutter: horz_offset,
+            numbers: (
+                self.bool_option("relativenumber"),
+                self.bool_option("line_numbers"),
+            ),
+            signs: self.signs(),
+            tabstop,
+            prompt: self.cursor().mode == Mode::Primitive(PrimitiveMode::Prompt),
+            filetype: self.str_option("filetype").to_owned(),
+            highlight: self.bool_option("highlight"),
+            line_marker: self.bool_option("line_marker"),
+            search: search.clone(),
+        };
 
-        // Redraw window
-        self.window.set(Color::rgb(25, 25, 25));
+        let full = match self.redraw_task {
+            RedrawTask::Full => true,
+            _ => self.screen.params.as_ref() != Some(&params),
+        };
+        if full {
+            self.window.set(color(self.theme.bg(Group::Normal)));
+        }
 
-        let mut scr_lines: usize = 0;
-        let mut scr_chars: usize = 0;
+        // The lines, which may have changed since the last frame
+        let mut dirty = self.redraw_task.lines();
+        if let Some(damage) = self.screen.damage.take() {
+            dirty.push(damage..usize::MAX);
+        }
 
-        self.cursor_in_window(max_horz_chars, max_vert_chars);
+        let (pos_x, pos_y) = self.pos();
+        // The display column of the cursor, i.e. with tabs expanded
+        let pos_x = self.display_x((pos_x, pos_y));
 
-        let (_scroll_x, scroll_y) = {
+        // The positions of the cursors in display columns, the current one first
+        let cursors: Vec<(usize, usize)> = {
             let current_buffer = self.buffers.current_buffer_info();
-
-            (current_buffer.scroll_x, current_buffer.scroll_y)
+            let current_cursor = current_buffer.current_cursor as usize;
+
+            iter::once((pos_x, pos_y))
+                .chain(
+                    current_buffer
+                        .cursors
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, _)| i != current_cursor)
+                        .map(|(_, c)| self.bound((c.x, c.y), false))
+                        .map(|(x, y)| (self.display_x((x, y)), y)),
+                )
+                .collect()
         };
+        let cursor_lines: Vec<usize> = cursors.iter().map(|&(_, y)| y).collect();
 
-        let (pos_x, pos_y) = self.pos();
+        // The visual selection as (line, display column) pairs, with both ends included
+        let selection = self.visual_region().map(|region| {
+            let (start, end) = (region.start, region.end);
+            if region.kind == MotionType::Linewise {
+                ((start.1, 0), (end.1, usize::MAX))
+            } else {
+                // The end covers every column of a wide char
+                (
+                    (start.1, self.display_x(start)),
+                    (end.1, self.display_x((end.0 + 1, end.1)) - 1),
+                )
+            }
+        });
+        let selection_lines = selection.map(|((sy, _), (ey, _))| (sy, ey));
 
-        let (window_pos_x, window_pos_y) =
-            self.coords_to_window_coords((pos_x, pos_y), max_horz_chars);
+        for &y in cursor_lines.iter().chain(self.screen.cursor_lines.iter()) {
+            dirty.push(y..y + 1);
+        }
+        // Relative line numbers change with the line of the cursor
+        if params.numbers.0 && self.screen.cursor_lines.first() != Some(&pos_y) {
+            dirty.push(0..usize::MAX);
+        }
+        for &(sy, ey) in selection_lines.iter().chain(self.screen.selection.iter()) {
+            dirty.push(sy..ey + 1);
+        }
 
-        self.window.set(Color::rgb(25, 25, 25));
+        let rows = self.screen_rows();
+        let cursor_row = self.row_of_col(pos_y, pos_x);
 
-        if self.options.line_marker {
-            self.window.rect(
-                0,
-                ((window_pos_y + vert_offset) * self.char_height) as i32,
-                w as u32,
-                16,
-                Color::rgb(45, 45, 45),
-            );
-        }
+        // Rows showing something else than before, or a line which may have changed
+        let damaged: Vec<usize> = (0..rows.len())
+            .filter(|&i| {
+                full || self.screen.rows.get(i) != Some(&rows[i])
+                    || rows[i].is_some_and(|(y, _)| dirty.iter().any(|r| r.contains(&y)))
+            })
+            .collect();
 
-        self.window.rect(
-            ((window_pos_x + horz_offset) * self.char_width) as i32,
-            ((window_pos_y + vert_offset) * self.char_height) as i32,
-            self.char_width as u32,
-            self.char_height as u32,
-            Color::rgb(255, 255, 255),
-        );
+        let damaged_lines = damaged.iter().filter_map(|&i| rows[i]).map(|(y, _)| y);
+        let first_line = damaged_lines.clone().min().unwrap_or(0);
+        let highlights = match damaged_lines.max() {
+            Some(last_line) => self.highlight_lines(first_line, last_line + 1),
+            None => Vec::new(),
+        };
 
-        let mut string = false;
+        self.screen.hashes.resize(rows.len(), 0);
 
-        'outer: for (y, row) in self
-            .buffers
-            .current_buffer()
-            .lines_from(scroll_y)
-            .enumerate()
-        {
-            // Print line numbers
-            if self.options.line_numbers {
-                let mut line_number = scroll_y + y as usize + 1;
-                // The amount of digits for this line number
-                let mut digit_nr: usize = 0;
-                while line_number >= 10usize.pow(digit_nr as u32) {
-                    digit_nr += 1;
-                }
-                // Print the digits for this line number
-                for i in 1..digit_nr + 1 {
-                    let digit = ((line_number % 10) as u8 + ('0' as u32) as u8) as char;
-                    line_number = (line_number - line_number % 10) / 10 as usize;
-                    self.window.char(
-                        (self.char_width * (horz_offset - 1 - i)) as i32,
-                        (self.char_height * (scr_lines + vert_offset)) as i32,
-                        digit,
-                        Color::rgb(255, 255, 0),
-                    );
+        for i in damaged {
+            let line = rows[i];
+
+            let row_bg = if self.bool_option("line_marker") && line == Some((pos_y, cursor_row)) {
+                self.theme.bg(Group::CursorLine)
+            } else {
+                self.theme.bg(Group::Normal)
+            };
+            let gutter = self.gutter_cells(line, pos_y, &params.signs);
+            let cells = match line {
+                Some((y, seg)) => {
+                    let span = self.row_span(y, seg);
+                    self.row_cells(
+                        y,
+                        span,
+                        highlights.get(y - first_line),
+                        &cursors,
+                        selection,
+                        search.as_deref(),
+                    )
                 }
+                None => Vec::new(),
+            };
+
+            let mut hasher = DefaultHasher::new();
+            (row_bg, &gutter, &cells).hash(&mut hasher);
+            let hash = hasher.finish();
+            if !full && self.screen.hashes[i] == hash {
+                continue;
             }
-            for (x, c) in row
-                .chars()
-                .flat_map(|c| {
-                    if c == '\t' {
-                        iter::repeat(' ').take(4)
-                    } else {
-                        iter::repeat(c).take(1)
-                    }
-                })
-                .enumerate()
-            {
-                // New screen line
-                if scr_chars >= max_horz_chars {
-                    scr_chars = 0;
-                    scr_lines += 1;
-                    if scr_lines > max_vert_chars {
-                        break 'outer;
-                    }
-                }
-
-                // TODO: Move outta here
-                let color = if self.options.highlight {
-                    match c {
-                        '\'' | '"' => {
-                            string = !string;
-                            (226, 225, 167) //(167, 222, 156)
-                        }
-                        _ if string => (226, 225, 167), //(167, 222, 156)
-                        '!' | '@' | '#' | '$' | '%' | '^' | '&' | '|' | '*' | '+' | '-' | '/'
-                        | ':' | '=' | '<' | '>' => (198, 83, 83), //(228, 190, 175), //(194, 106, 71),
-                        '.' | ',' => (241, 213, 226),
-                        '(' | ')' | '[' | ']' | '{' | '}' => (164, 212, 125), //(195, 139, 75),
-                        '0'..='9' => (209, 209, 177),
-                        _ => (255, 255, 255),
-                    }
-                } else {
-                    (255, 255, 255)
-                };
-
-                if pos_x == x && (pos_y - scroll_y) == y {
-                    self.window.char(
-                        (self.char_width * (scr_chars + horz_offset)) as i32,
-                        (self.char_height * (scr_lines + vert_offset)) as i32,
-                        c,
-                        Color::rgb(color.0 / 3, color.1 / 3, color.2 / 3),
-                    );
-                } else {
-                    self.window.char(
-                        (self.char_width * (scr_chars + horz_offset)) as i32,
-                        (self.char_height * (scr_lines + vert_offset)) as i32,
-                        c,
-                        Color::rgb(color.0, color.1, color.2),
+            self.screen.hashes[i] = hash;
+
+            let top = ((i + vert_offset) * self.char_height) as i32;
+            self.window
+                .rect(0, top, w as u32, self.char_height as u32, color(row_bg));
+            for (x, &(c, fg, bg)) in gutter.iter().chain(cells.iter()).enumerate() {
+                let left = (x * self.char_width) as i32;
+                if let Some(bg) = bg {
+                    self.window.rect(
+                        left,
+                        top,
+                        self.char_width as u32,
+                        self.char_height as u32,
+                        color(bg),
                     );
                 }
-                scr_chars += 1;
-            }
-            scr_lines += 1;
-            scr_chars = 0;
-            if scr_lines > max_vert_chars {
-                break;
+                self.window.char(left, top, c, color(fg));
             }
         }
+
+        self.screen.params = Some(params);
+        self.screen.rows = rows;
+        self.screen.cursor_lines = cursor_lines;
+        self.screen.selection = selection_lines;
+
         self.redraw_status_bar();
         self.redraw_task = RedrawTask::None;
         self.window.sync();
     }
 
-    fn coords_to_window_coords(
-        &mut self,
-        point: (usize, usize),
-        max_horz_chars: usize,
-    ) -> (usize, usize) {
-        let (_, scroll_y) = {
-            let current_buffer = self.buffers.current_buffer_info();
+    /// Get the cells of the gutter before a screen row, showing the given line and wrapped
+    /// segment of the line, if any. The sign and the number are only shown on the first row of a
+    /// line.
+    fn gutter_cells(
+        &self,
+        line: Option<(usize, usize)>,
+        cursor_y: usize,
+        signs: &[(usize, char, Group)],
+    ) -> Vec<Cell> {
+        let first = line.filter(|&(_, seg)| seg == 0).map(|(y, _)| y);
+        let mut cells = Vec::new();
+
+        let sign_width = self.sign_width();
+        if sign_width > 0 {
+            let bg = self.theme.style(Group::SignColumn).bg;
+            let (c, group) = signs
+                .iter()
+                .find(|&&(y, _, _)| Some(y) == first)
+                .map_or((' ', Group::SignColumn), |&(_, c, group)| (c, group));
+            cells.push((c, self.theme.fg(group), bg));
+            cells.resize(sign_width, (' ', self.theme.fg(Group::SignColumn), bg));
+        }
 
-            (current_buffer.scroll_x, current_buffer.scroll_y)
-        };
+        let number_width = self.number_width();
+        if number_width > 0 {
+            let (number, group) = match first {
+                Some(y) => self.line_number(y, cursor_y),
+                None => (String::new(), Group::LineNr),
+            };
+            // The number of the cursor line falls back to the background of the others
+            let bg = self
+                .theme
+                .style(group)
+                .bg
+                .or(self.theme.style(Group::LineNr).bg);
+            let fg = self.theme.fg(group);
+            cells.extend(number.chars().map(|c| (c, fg, bg)));
+            cells.resize(sign_width + number_width, (' ', fg, bg));
+        }
 
-        let to_y = point.1 - scroll_y;
+        cells
+    }
 
-        let mut ret_y = 0;
+    /// Get the cells of a screen row, showing the display columns of line `y` from a span, as
+    /// given by `row_span`.
+    fn row_cells(
+        &self,
+        y: usize,
+        (start, end, prefix): (usize, usize, usize),
+        kinds: Option<&Vec<Highlight>>,
+        cursors: &[(usize, usize)],
+        selection: Option<((usize, usize), (usize, usize))>,
+        search: Option<&str>,
+    ) -> Vec<Cell> {
+        let tabstop = self.int_option("tabstop");
+        let line = &self.buffers.current_buffer()[y];
+        let (cols, _) = self.text_area();
+        let width = min(end - start, cols - prefix);
+
+        // The matches of the search pattern, as grapheme cluster ranges
+        let matches: Vec<(usize, usize)> = match search {
+            Some(pattern) => line
+                .match_indices(pattern)
+                .map(|(b, m)| (grapheme_index(line, b), grapheme_index(line, b + m.len())))
+                .collect(),
+            None => Vec::new(),
+        };
 
-        let ret_x = point.0 % max_horz_chars;
-        for (y, row) in self
-            .buffers
-            .current_buffer()
-            .lines_from(scroll_y)
-            .enumerate()
-        {
-            if to_y > y {
-                ret_y += row.len() / max_horz_chars + 1;
-            } else {
-                ret_y += point.0 / max_horz_chars;
+        // The index of the grapheme cluster, the index of its first char (which the highlighting
+        // is given by) and the char shown in each display column. Only the first column of a
+        // cluster shows its first char, and combining marks are not drawn.
+        let (mut col, mut first) = (0, 0);
+        let mut chars: Vec<(usize, usize, char)> = Vec::new();
+        for (n, g) in line.graphemes(true).enumerate() {
+            if col >= start + width {
                 break;
             }
+            let width = grapheme_width(g, col, tabstop);
+            let c = match g.chars().next() {
+                Some('\t') | None => ' ',
+                Some(c) => c,
+            };
+            for i in max(col, start)..col + width {
+                chars.push((n, first, if i == col { c } else { ' ' }));
+            }
+            col += width;
+            first += g.chars().count();
         }
-        (ret_x, ret_y)
-    }
-
-    // Ensure that the cursor is visible
-    fn cursor_in_window(&mut self, max_horz_chars: usize, max_vert_chars: usize) {
-        let (_pos_x, pos_y) = self.pos();
-        if self.buffers.current_buffer_info().scroll_y > 0
-            && pos_y <= self.buffers.current_buffer_info().scroll_y
-        {
-            self.buffers.current_buffer_info_mut().scroll_y =
-                if pos_y == 0 { pos_y } else { pos_y - 1 };
-            return;
+        chars.truncate(width);
+
+        // Cursors after the end of the line are drawn on blank cells
+        let len = grapheme_count(line);
+        let char_len = line.chars().count();
+        for &(x, cy) in cursors {
+            if cy == y && start <= x && x < start + width {
+                while chars.len() <= x - start {
+                    chars.push((len, char_len, ' '));
+                }
+            }
         }
 
-        let scroll_y = self.buffers.current_buffer_info().scroll_y;
-        let mut line_counter = 0;
-        let mut result_y = 0;
-
-        for (y, row) in self
-            .buffers
-            .current_buffer()
-            .lines_from(pos_y + 1)
-            .rev()
+        let mut cells: Vec<Cell> = chars
+            .into_iter()
             .enumerate()
-        {
-            if pos_y - y < scroll_y {
-                return;
+            .map(|(i, (n, first, c))| {
+                let x = start + i;
+                let group = kinds
+                    .and_then(|kinds| kinds.get(first))
+                    .map_or(Highlight::Normal, |&kind| kind)
+                    .group();
+
+                // The selection and search matches are drawn over the syntax highlighting
+                let overlay =
+                    if selection.is_some_and(|(start, end)| start <= (y, x) && (y, x) <= end) {
+                        Some(Group::Visual)
+                    } else if matches.iter().any(|&(start, end)| start <= n && n < end) {
+                        Some(Group::Search)
+                    } else {
+                        None
+                    };
+                let fg = overlay
+                    .and_then(|overlay| self.theme.style(overlay).fg)
+                    .unwrap_or_else(|| self.theme.fg(group));
+
+                match cursors.iter().position(|&p| p == (x, y)) {
+                    Some(cursor) => {
+                        let group = if cursor == 0 {
+                            Group::Cursor
+                        } else {
+                            Group::InactiveCursor
+                        };
+                        // Without a color of its own, the cursor shows a dimmed char
+                        let fg =
+                            self.theme
+                                .style(group)
+                                .fg
+                                .unwrap_or((fg.0 / 3, fg.1 / 3, fg.2 / 3));
+                        (c, fg, Some(self.theme.bg(group)))
+                    }
+                    None => {
+                        let bg = match overlay {
+                            Some(overlay) => Some(self.theme.bg(overlay)),
+                            None => self.theme.style(group).bg,
+                        };
+                        (c, fg, bg)
+                    }
+                }
+            })
+            .collect();
+
+        // Mark wrapped rows and truncated lines
+        let marker = self.theme.fg(Group::NonText);
+        if prefix > 0 {
+            let showbreak = self.str_option("showbreak").chars().take(prefix);
+            cells.splice(0..0, showbreak.map(|c| (c, marker, None)));
+        }
+        if !self.bool_option("wrap") {
+            let line_width = display_width(line, tabstop);
+            if start > 0 && line_width > 0 && !cursors.contains(&(start, y)) {
+                if cells.is_empty() {
+                    cells.push((' ', marker, None));
+                }
+                cells[0] = ('<', marker, None);
             }
-            line_counter += row.len() / max_horz_chars + 1;
-            if line_counter > max_vert_chars {
-                result_y = pos_y - y;
-                break;
+            if line_width > start + width && !cursors.contains(&(start + width - 1, y)) {
+                let last = cells.len() - 1;
+                cells[last] = ('>', marker, None);
             }
         }
-        self.buffers.current_buffer_info_mut().scroll_y = result_y;
+
+        cells
     }
 
     /// Redraw the status bar
@@ -250,7 +417,7 @@ impl Editor {
             },
             w,
             18,
-            Color::rgba(74, 74, 74, 255),
+            color(self.theme.bg(Group::StatusLine)),
         );
 
         self.draw_status_bar();
@@ -260,7 +427,7 @@ impl Editor {
                 n as i32 * 8,
                 h as i32 - 16 - 1,
                 c,
-                Color::rgb(255, 255, 255),
+                color(self.theme.fg(Group::Prompt)),
             );
         }
 
@@ -278,13 +445,43 @@ impl Editor {
             .buffers
             .current_buffer_info()
             .title
-            .as_ref()
-            .map(|s| s.as_str())
+            .as_deref()
             .unwrap_or("");
+        let filetype = self.str_option("filetype");
+        let current_title = if filetype.is_empty() {
+            current_title.to_owned()
+        } else {
+            format!("{} [{}]", current_title, filetype)
+        };
+
+        let mode_text = {
+            let current_buffer = self.buffers.current_buffer_info();
+            if current_buffer.cursors.len() > 1 {
+                // Show the index and mode of every cursor, the current one in brackets
+                let cursors = current_buffer
+                    .cursors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let short = &c.mode.to_string()[..1];
+                        if i == current_buffer.current_cursor as usize {
+                            format!("[{}:{}]", i, short)
+                        } else {
+                            format!("{}:{}", i, short)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                format!("{} {}", self.status_bar.mode, cursors)
+            } else {
+                self.status_bar.mode.to_owned()
+            }
+        };
 
         let items = [
-            (self.status_bar.mode, 0, 4),
-            (current_title, 1, 4),
+            (mode_text.as_str(), 0, 4),
+            (current_title.as_str(), 1, 4),
             (&self.status_bar.cmd, 2, 4),
             (&self.status_bar.msg, 3, 4),
         ];
@@ -311,7 +508,7 @@ impl Editor {
                         }
                     },
                     c,
-                    Color::rgb(255, 255, 255),
+                    color(self.theme.fg(Group::StatusLine)),
                 );
             }
         }
@@ -336,6 +533,12 @@ pub struct StatusBar {
     pub msg: String,
 }
 
+impl Default for StatusBar {
+    fn default() -> Self {
+        StatusBar::new()
+    }
+}
+
 impl StatusBar {
     /// Create new status bar
     pub fn new() -> Self {
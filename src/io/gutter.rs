@@ -0,0 +1,90 @@
+use edit::buffer::TextBuffer;
+use io::theme::Group;
+use state::editor::Editor;
+
+use std::cmp::max;
+
+/// The width of the sign column: A sign and a blank.
+const SIGN_WIDTH: usize = 2;
+
+impl Editor {
+    /// Get the signs of the current buffer, as the line, the sign and its group, sorted by line.
+    /// Marks are shown by their name, and the lines in the change list by `~` (while there are
+    /// unsaved changes). Marks take precedence over changes.
+    pub fn signs(&self) -> Vec<(usize, char, Group)> {
+        let buffer = self.buffers.current_buffer_info();
+        let mut signs: Vec<(usize, char, Group)> = Vec::new();
+
+        let global = self
+            .global_marks
+            .iter()
+            .filter(|&(_, mark)| buffer.title.as_ref() == Some(&mark.path))
+            .map(|(&c, mark)| (c, mark.pos));
+        for (c, (_, y)) in buffer.marks.iter().map(|(&c, &pos)| (c, pos)).chain(global) {
+            signs.push((y, c, Group::MarkSign));
+        }
+        if buffer.dirty {
+            for &(_, y) in buffer.changes.positions() {
+                signs.push((y, '~', Group::ChangeSign));
+            }
+        }
+
+        // Keep the first sign of each line, marks in alphabetical order
+        signs.sort_by_key(|&(y, c, group)| (y, group != Group::MarkSign, c));
+        signs.dedup_by_key(|&mut (y, _, _)| y);
+        signs
+    }
+
+    /// Get the width of the sign column, or 0 if it is not shown.
+    pub fn sign_width(&self) -> usize {
+        let shown = match self.str_option("signcolumn") {
+            "yes" => true,
+            "auto" => !self.signs().is_empty(),
+            _ => false,
+        };
+        if shown {
+            SIGN_WIDTH
+        } else {
+            0
+        }
+    }
+
+    /// Get the width of the line number column (the numbers and a blank after them), or 0 if
+    /// line numbers are not shown.
+    pub fn number_width(&self) -> usize {
+        if self.bool_option("line_numbers") || self.bool_option("relativenumber") {
+            let digits = self.buffers.current_buffer().len().to_string().len();
+            max(digits, 2) + 1
+        } else {
+            0
+        }
+    }
+
+    /// Get the width of the gutter, i.e. the sign column and the line numbers.
+    pub fn gutter_width(&self) -> usize {
+        self.sign_width() + self.number_width()
+    }
+
+    /// Get the line number shown before line `y`, given the line of the cursor, and its group.
+    /// With `relativenumber`, the distance to the line of the cursor is shown instead, except
+    /// for the line of the cursor itself, if `line_numbers` is set as well.
+    pub fn line_number(&self, y: usize, cursor_y: usize) -> (String, Group) {
+        let width = self.number_width().saturating_sub(1);
+        let group = if y == cursor_y {
+            Group::CursorLineNr
+        } else {
+            Group::LineNr
+        };
+
+        let text = if !self.bool_option("relativenumber") {
+            format!("{:>1$}", y + 1, width)
+        } else if y == cursor_y && self.bool_option("line_numbers") {
+            // The absolute number of the cursor line is aligned left, to stand out
+            format!("{:<1$}", y + 1, width)
+        } else {
+            let distance = y.abs_diff(cursor_y);
+            format!("{:>1$}", distance, width)
+        };
+        (text, group)
+    }
+}
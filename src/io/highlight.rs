@@ -0,0 +1,453 @@
+use edit::buffer::TextBuffer;
+use io::theme::Group;
+use state::editor::{Buffer, Editor};
+use state::filetype::{Syntax, PLAIN_SYNTAX};
+
+use std::cmp::min;
+
+#[derive(Clone, Copy, PartialEq)]
+/// The kind of a highlighted piece of text.
+pub enum Highlight {
+    /// Text which is not highlighted.
+    Normal,
+    /// A keyword.
+    Keyword,
+    /// A type (a capitalized identifier).
+    Type,
+    /// A comment.
+    Comment,
+    /// A string or char literal.
+    String,
+    /// A number.
+    Number,
+    /// A lifetime (e.g. `'a`).
+    Lifetime,
+    /// An attribute (e.g. `#[derive(Clone)]`).
+    Attribute,
+    /// A heading (e.g. `# Title` in Markdown).
+    Heading,
+    /// An operator.
+    Operator,
+    /// Punctuation (`.` and `,`).
+    Punctuation,
+    /// A delimiter (parentheses, brackets and braces).
+    Delimiter,
+}
+
+impl Highlight {
+    /// The highlight group, which the text is colored with.
+    pub fn group(self) -> Group {
+        match self {
+            Highlight::Normal => Group::Normal,
+            Highlight::Keyword => Group::Keyword,
+            Highlight::Type => Group::Type,
+            Highlight::Comment => Group::Comment,
+            Highlight::String => Group::String,
+            Highlight::Number => Group::Number,
+            Highlight::Lifetime => Group::Lifetime,
+            Highlight::Attribute => Group::Attribute,
+            Highlight::Heading => Group::Heading,
+            Highlight::Operator => Group::Operator,
+            Highlight::Punctuation => Group::Punctuation,
+            Highlight::Delimiter => Group::Delimiter,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+/// The state of the highlighter between lines.
+pub enum LineState {
+    /// Not inside any multi-line construct.
+    Normal,
+    /// Inside a block comment, nested to the given depth.
+    Comment(usize),
+    /// Inside a string delimited by the given char.
+    String(char),
+    /// Inside a fenced block of code.
+    Code,
+}
+
+/// The highlighting state of a buffer: The state at the start of every line highlighted so far.
+pub struct HighlightCache {
+    /// The filetype, which the states were computed with.
+    filetype: String,
+    /// The state at the start of each line.
+    states: Vec<LineState>,
+}
+
+impl Default for HighlightCache {
+    fn default() -> HighlightCache {
+        HighlightCache::new()
+    }
+}
+
+impl HighlightCache {
+    /// Create a new empty cache.
+    pub fn new() -> HighlightCache {
+        HighlightCache {
+            filetype: String::new(),
+            states: Vec::new(),
+        }
+    }
+
+    /// Forget the states after a changed line, as they may depend on it.
+    pub fn invalidate(&mut self, line: usize) {
+        self.states.truncate(line + 1);
+    }
+}
+
+/// Check if the chars of a line continue with a given string at index `i`.
+fn starts_with(chars: &[char], i: usize, pat: &str) -> bool {
+    pat.chars()
+        .enumerate()
+        .all(|(n, c)| chars.get(i + n) == Some(&c))
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Highlight a line of a given syntax, starting in a given state. Returns the highlighting of
+/// every char in the line, and the state at the start of the next line.
+pub fn highlight_line(
+    syntax: &Syntax,
+    line: &str,
+    mut state: LineState,
+) -> (Vec<Highlight>, LineState) {
+    let chars: Vec<char> = line.chars().collect();
+    let len = chars.len();
+    let mut kinds = vec![Highlight::Normal; len];
+    let trimmed = line.trim_start();
+
+    // Whole-line constructs
+    let fence = syntax.code_fence.is_some_and(|f| trimmed.starts_with(f));
+    if state == LineState::Code {
+        let next = if fence {
+            LineState::Normal
+        } else {
+            LineState::Code
+        };
+        return (vec![Highlight::String; len], next);
+    }
+    if state == LineState::Normal {
+        if fence {
+            return (vec![Highlight::String; len], LineState::Code);
+        }
+        if syntax.headings.iter().any(|h| trimmed.starts_with(h)) {
+            return (vec![Highlight::Heading; len], LineState::Normal);
+        }
+    }
+
+    let mut i = 0;
+    while i < len {
+        let c = chars[i];
+
+        match state {
+            LineState::Comment(depth) => {
+                let (start, end) = syntax.block_comment.unwrap_or(("", ""));
+                let (skip, next) = if starts_with(&chars, i, end) {
+                    let next = if depth > 1 {
+                        LineState::Comment(depth - 1)
+                    } else {
+                        LineState::Normal
+                    };
+                    (end.chars().count(), next)
+                } else if syntax.nested_comments && starts_with(&chars, i, start) {
+                    (start.chars().count(), LineState::Comment(depth + 1))
+                } else {
+                    (1, state)
+                };
+
+                for kind in kinds[i..i + skip].iter_mut() {
+                    *kind = Highlight::Comment;
+                }
+                i += skip;
+                state = next;
+            }
+            LineState::String(quote) => {
+                kinds[i] = Highlight::String;
+                if Some(c) == syntax.escape && i + 1 < len {
+                    kinds[i + 1] = Highlight::String;
+                    i += 1;
+                } else if c == quote {
+                    state = LineState::Normal;
+                }
+                i += 1;
+            }
+            LineState::Code => unreachable!(),
+            LineState::Normal => {
+                let start = i;
+
+                let kind = if syntax
+                    .line_comments
+                    .iter()
+                    .any(|p| starts_with(&chars, i, p))
+                {
+                    i = len;
+                    Highlight::Comment
+                } else if let Some((open, _)) = syntax
+                    .block_comment
+                    .filter(|&(open, _)| starts_with(&chars, i, open))
+                {
+                    i += open.chars().count();
+                    state = LineState::Comment(1);
+                    Highlight::Comment
+                } else if syntax.attributes.iter().any(|p| starts_with(&chars, i, p)) {
+                    // Run to the matching bracket
+                    let mut depth = 0;
+                    while i < len {
+                        match chars[i] {
+                            '[' => depth += 1,
+                            ']' => depth -= 1,
+                            _ => {}
+                        }
+                        i += 1;
+                        if depth == 0 && chars[i - 1] == ']' {
+                            break;
+                        }
+                    }
+                    Highlight::Attribute
+                } else if c == '\'' && syntax.lifetimes {
+                    match (chars.get(i + 1), chars.get(i + 2)) {
+                        // An escaped char literal, e.g. '\n' or '\u{1F600}'
+                        (Some('\\'), _) => {
+                            i += 2;
+                            while i < len && chars[i] != '\'' {
+                                i += 1;
+                            }
+                            i = min(i + 1, len);
+                            Highlight::String
+                        }
+                        (Some(_), Some('\'')) => {
+                            i += 3;
+                            Highlight::String
+                        }
+                        (Some(&n), _) if is_ident(n) => {
+                            i += 1;
+                            while i < len && is_ident(chars[i]) {
+                                i += 1;
+                            }
+                            Highlight::Lifetime
+                        }
+                        _ => {
+                            i += 1;
+                            Highlight::Normal
+                        }
+                    }
+                } else if syntax.strings.contains(&c) {
+                    i += 1;
+                    state = LineState::String(c);
+                    Highlight::String
+                } else if is_ident(c) {
+                    while i < len && is_ident(chars[i]) {
+                        i += 1;
+                    }
+                    let word: String = chars[start..i].iter().collect();
+
+                    if c.is_ascii_digit() {
+                        // Decimal points belong to the number, unless followed by a non-digit
+                        // (as in ranges or method calls)
+                        while i + 1 < len && chars[i] == '.' && chars[i + 1].is_ascii_digit() {
+                            i += 1;
+                            while i < len && is_ident(chars[i]) {
+                                i += 1;
+                            }
+                        }
+                        if syntax.symbols {
+                            Highlight::Number
+                        } else {
+                            Highlight::Normal
+                        }
+                    } else if syntax.keywords.contains(&word.as_str()) {
+                        Highlight::Keyword
+                    } else if syntax.capitalized_types && c.is_uppercase() {
+                        Highlight::Type
+                    } else {
+                        Highlight::Normal
+                    }
+                } else {
+                    i += 1;
+                    match c {
+                        _ if !syntax.symbols => Highlight::Normal,
+                        '!' | '@' | '#' | '$' | '%' | '^' | '&' | '|' | '*' | '+' | '-' | '/'
+                        | ':' | '=' | '<' | '>' | '?' | '~' | ';' => Highlight::Operator,
+                        '.' | ',' => Highlight::Punctuation,
+                        '(' | ')' | '[' | ']' | '{' | '}' => Highlight::Delimiter,
+                        _ => Highlight::Normal,
+                    }
+                };
+
+                for k in kinds[start..i].iter_mut() {
+                    *k = kind;
+                }
+            }
+        }
+    }
+
+    if let LineState::String(_) = state {
+        if !syntax.multiline_strings {
+            state = LineState::Normal;
+        }
+    }
+
+    (kinds, state)
+}
+
+impl Editor {
+    /// Get the syntax to highlight the current buffer with, if any.
+    pub fn syntax(&self) -> Option<&'static Syntax> {
+        if !self.bool_option("highlight") {
+            return None;
+        }
+        match self.filetype() {
+            Some(ft) => ft.syntax.as_ref(),
+            None => Some(&PLAIN_SYNTAX),
+        }
+    }
+
+    /// Highlight the lines from `from` to `to` (exclusive) of the current buffer. The state at the
+    /// start of each line is cached, so the cache must be invalidated when lines change. Returns
+    /// nothing if the buffer is not highlighted.
+    pub fn highlight_lines(&mut self, from: usize, to: usize) -> Vec<Vec<Highlight>> {
+        let syntax = match self.syntax() {
+            Some(syntax) => syntax,
+            None => return Vec::new(),
+        };
+        let filetype = self.str_option("filetype").to_owned();
+
+        let Buffer {
+            ref raw_buffer,
+            ref mut highlight,
+            ..
+        } = *self.buffers.current_buffer_info_mut();
+
+        // Every state depends on the filetype
+        if highlight.filetype != filetype {
+            highlight.filetype = filetype;
+            highlight.states.clear();
+        }
+
+        let to = min(to, raw_buffer.len());
+        if highlight.states.is_empty() {
+            highlight.states.push(LineState::Normal);
+        }
+        while highlight.states.len() < to {
+            let y = highlight.states.len() - 1;
+            let (_, next) = highlight_line(syntax, &raw_buffer[y], highlight.states[y]);
+            highlight.states.push(next);
+        }
+
+        (from..to)
+            .map(|y| highlight_line(syntax, &raw_buffer[y], highlight.states[y]).0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state::filetype::find_filetype;
+
+    fn syntax(name: &str) -> &'static Syntax {
+        find_filetype(name).unwrap().syntax.as_ref().unwrap()
+    }
+
+    /// Highlight a line, describing the kind of every char by a letter.
+    fn kinds(syntax: &Syntax, line: &str, state: LineState) -> (String, LineState) {
+        let (kinds, next) = highlight_line(syntax, line, state);
+        let letters = kinds
+            .iter()
+            .map(|&k| match k {
+                Highlight::Normal => '.',
+                Highlight::Keyword => 'k',
+                Highlight::Type => 't',
+                Highlight::Comment => 'c',
+                Highlight::String => 's',
+                Highlight::Number => 'n',
+                Highlight::Lifetime => 'l',
+                Highlight::Attribute => 'a',
+                Highlight::Heading => 'h',
+                Highlight::Operator => 'o',
+                Highlight::Punctuation => 'p',
+                Highlight::Delimiter => 'd',
+            })
+            .collect();
+        (letters, next)
+    }
+
+    #[test]
+    fn keywords_types_and_numbers() {
+        let (k, next) = kinds(syntax("rust"), "let x: Vec = 1.5;", LineState::Normal);
+        assert_eq!(k, "kkk..o.ttt.o.nnno");
+        assert_eq!(next, LineState::Normal);
+        // A range is not a decimal point
+        assert_eq!(kinds(syntax("rust"), "0..2", LineState::Normal).0, "nppn");
+    }
+
+    #[test]
+    fn nested_block_comments_carry_their_depth() {
+        let rust = syntax("rust");
+        let (k, next) = kinds(rust, "a /* b /* c */ d", LineState::Normal);
+        assert_eq!(k, "..cccccccccccccc");
+        assert_eq!(next, LineState::Comment(1));
+
+        let (k, next) = kinds(rust, "*/ x", next);
+        assert_eq!(k, "cc..");
+        assert_eq!(next, LineState::Normal);
+
+        let (_, next) = kinds(rust, "/* /* /*", LineState::Normal);
+        assert_eq!(next, LineState::Comment(3));
+    }
+
+    #[test]
+    fn block_comments_do_not_nest_without_support() {
+        let css = Syntax {
+            block_comment: Some(("/*", "*/")),
+            ..PLAIN_SYNTAX
+        };
+        let (k, next) = kinds(&css, "/* /* */ x", LineState::Normal);
+        assert_eq!(k, "cccccccc..");
+        assert_eq!(next, LineState::Normal);
+    }
+
+    #[test]
+    fn strings_lifetimes_and_char_literals() {
+        let rust = syntax("rust");
+        assert_eq!(kinds(rust, r#""a\"b" x"#, LineState::Normal).0, "ssssss..");
+        assert_eq!(
+            kinds(rust, "&'a 'b' '\\n'", LineState::Normal).0,
+            "oll.sss.ssss"
+        );
+
+        // Rust strings span lines, plain ones end with the line
+        assert_eq!(
+            kinds(rust, "\"abc", LineState::Normal).1,
+            LineState::String('"')
+        );
+        assert_eq!(
+            kinds(&PLAIN_SYNTAX, "\"abc", LineState::Normal).1,
+            LineState::Normal
+        );
+        assert_eq!(kinds(rust, "c\" d", LineState::String('"')).0, "ss..");
+    }
+
+    #[test]
+    fn attributes_run_to_the_matching_bracket() {
+        let (k, _) = kinds(syntax("rust"), "#[cfg(all(x, y[0]))] fn", LineState::Normal);
+        assert_eq!(k, "aaaaaaaaaaaaaaaaaaaa.kk");
+    }
+
+    #[test]
+    fn markdown_headings_and_code_fences() {
+        let md = syntax("markdown");
+        assert_eq!(kinds(md, "# Title", LineState::Normal).0, "hhhhhhh");
+        let (k, next) = kinds(md, "```rust", LineState::Normal);
+        assert_eq!(k, "sssssss");
+        assert_eq!(next, LineState::Code);
+        assert_eq!(
+            kinds(md, "# not a heading", LineState::Code).1,
+            LineState::Code
+        );
+        assert_eq!(kinds(md, "```", LineState::Code).1, LineState::Normal);
+    }
+}
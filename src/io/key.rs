@@ -1,7 +1,8 @@
+use io::key_state::KeyState;
 #[cfg(feature = "orbital")]
 use orbclient::{KeyEvent, K_BKSP, K_DOWN, K_ESC, K_LEFT, K_RIGHT, K_TAB, K_UP};
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 /// A key
 pub enum Key {
     /// Printable character.
@@ -62,9 +63,208 @@ impl Key {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 /// A command, i.e. a key together with information on the modifiers.
 pub struct Cmd {
     /// The key associated with the command.
     pub key: Key,
+    /// Ctrl modifier.
+    pub ctrl: bool,
+    /// Alt modifier.
+    pub alt: bool,
+    /// Shift modifier. This is only set for keys, which are not changed by shift themselves (i.e.
+    /// not for printable characters other than space).
+    pub shift: bool,
+}
+
+impl Cmd {
+    /// Create a new command from a key without modifiers.
+    pub fn new(key: Key) -> Cmd {
+        Cmd {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    /// Create a new command from a key and the currently active modifiers.
+    pub fn from_state(key: Key, state: &KeyState) -> Cmd {
+        Cmd {
+            key,
+            ctrl: state.ctrl,
+            alt: state.alt,
+            shift: state.shift && shift_significant(key),
+        }
+    }
+
+    /// Convert the command to its key notation, e.g. `<C-o>` or `<S-Space>`.
+    pub fn to_notation(self) -> String {
+        let name = match self.key {
+            Key::Char(' ') => "Space".to_owned(),
+            Key::Char('\n') => "CR".to_owned(),
+            Key::Char('<') => "lt".to_owned(),
+            Key::Char(c) if !(self.ctrl || self.alt || self.shift) => return c.to_string(),
+            Key::Char(c) => c.to_string(),
+            Key::Backspace => "BS".to_owned(),
+            Key::Escape => "Esc".to_owned(),
+            Key::Left => "Left".to_owned(),
+            Key::Right => "Right".to_owned(),
+            Key::Up => "Up".to_owned(),
+            Key::Down => "Down".to_owned(),
+            Key::Tab => "Tab".to_owned(),
+            Key::Null => "Nul".to_owned(),
+            Key::Quit => "Quit".to_owned(),
+            Key::Unknown(n) => format!("#{}", n),
+        };
+
+        format!(
+            "<{}{}{}{}>",
+            if self.ctrl { "C-" } else { "" },
+            if self.alt { "A-" } else { "" },
+            if self.shift { "S-" } else { "" },
+            name
+        )
+    }
+}
+
+/// Is the shift modifier significant for a key, i.e. does it not change the key itself?
+fn shift_significant(key: Key) -> bool {
+    match key {
+        Key::Char(c) => c == ' ' || c == '\n',
+        _ => true,
+    }
+}
+
+/// Parse a sequence of keys in key notation, e.g. `gU`, `<C-o>` or `jk<Esc>`.
+///
+/// Special keys and modifiers are written in angle brackets: `<C-x>` (ctrl), `<A-x>` or `<M-x>`
+/// (alt), `<S-x>` (shift), `<Space>`, `<CR>`, `<Esc>`, `<BS>`, `<Tab>`, `<Left>`, `<Right>`,
+/// `<Up>`, `<Down>` and `<lt>` (a literal `<`). A `<` not starting a valid key name is taken
+/// literally.
+pub fn parse_keys(s: &str) -> Result<Vec<Cmd>, String> {
+    let mut keys = Vec::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                if let Some(cmd) = parse_special(&rest[1..end]) {
+                    keys.push(cmd);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        keys.push(Cmd::new(Key::Char(c)));
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if keys.is_empty() {
+        Err("No keys given".to_owned())
+    } else {
+        Ok(keys)
+    }
+}
+
+/// Parse the inner part of a `<...>` key.
+fn parse_special(s: &str) -> Option<Cmd> {
+    let mut cmd = Cmd::new(Key::Null);
+    let mut name = s;
+
+    loop {
+        let lower = name.to_lowercase();
+        if lower.starts_with("c-") && name.len() > 2 {
+            cmd.ctrl = true;
+        } else if (lower.starts_with("a-") || lower.starts_with("m-")) && name.len() > 2 {
+            cmd.alt = true;
+        } else if lower.starts_with("s-") && name.len() > 2 {
+            cmd.shift = true;
+        } else {
+            break;
+        }
+        name = &name[2..];
+    }
+
+    cmd.key = match name.to_lowercase().as_str() {
+        "space" => Key::Char(' '),
+        "cr" | "enter" | "return" => Key::Char('\n'),
+        "lt" => Key::Char('<'),
+        "esc" => Key::Escape,
+        "bs" => Key::Backspace,
+        "tab" => Key::Tab,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if cmd.shift => Key::Char(c.to_ascii_uppercase()),
+                (Some(c), None) if cmd.ctrl || cmd.alt => Key::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    cmd.shift = cmd.shift && shift_significant(cmd.key);
+
+    Some(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse keys and write them back in (normalized) key notation.
+    fn notation(s: &str) -> String {
+        parse_keys(s)
+            .unwrap()
+            .iter()
+            .map(|k| k.to_notation())
+            .collect()
+    }
+
+    #[test]
+    fn plain_chars_are_single_keys() {
+        assert_eq!(parse_keys("gU").unwrap().len(), 2);
+        assert_eq!(notation("jk"), "jk");
+        assert_eq!(notation("ä漢"), "ä漢");
+        assert!(parse_keys("").is_err());
+    }
+
+    #[test]
+    fn modifiers_and_special_keys() {
+        let cmd = parse_keys("<C-x>").unwrap()[0];
+        assert_eq!(cmd.key, Key::Char('x'));
+        assert!(cmd.ctrl && !cmd.alt && !cmd.shift);
+        assert_eq!(notation("<c-o><M-x><A-x>"), "<C-o><A-x><A-x>");
+        assert_eq!(notation("<C-A-x>"), "<C-A-x>");
+        assert_eq!(
+            notation("<Esc><cr><Enter><BS><Tab><Space>"),
+            "<Esc><CR><CR><BS><Tab><Space>"
+        );
+        assert_eq!(
+            notation("<Left><Right><Up><Down>"),
+            "<Left><Right><Up><Down>"
+        );
+    }
+
+    #[test]
+    fn shift_only_applies_where_significant() {
+        // Shift changes printable chars themselves
+        assert_eq!(notation("<S-x>"), "X");
+        assert_eq!(notation("<S-Space><S-Left>"), "<S-Space><S-Left>");
+    }
+
+    #[test]
+    fn lt_and_invalid_names_are_literal() {
+        assert_eq!(notation("<lt>"), "<lt>");
+        assert_eq!(parse_keys("<lt>").unwrap()[0], Cmd::new(Key::Char('<')));
+        assert_eq!(notation("<lt>Esc>"), "<lt>Esc>");
+        // Not a key name, so every char is taken literally
+        assert_eq!(parse_keys("<foo>").unwrap().len(), 5);
+        assert_eq!(parse_keys("a<").unwrap().len(), 2);
+        assert_eq!(parse_keys("<C->").unwrap().len(), 4);
+    }
 }
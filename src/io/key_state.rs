@@ -16,6 +16,16 @@ pub struct KeyState {
     pub alt: bool,
     /// Shift modifier.
     pub shift: bool,
+    /// The position of the mouse in the window, in pixels.
+    pub mouse: (i32, i32),
+    /// Whether the left mouse button is pressed.
+    pub left_button: bool,
+}
+
+impl Default for KeyState {
+    fn default() -> KeyState {
+        KeyState::new()
+    }
 }
 
 impl KeyState {
@@ -25,6 +35,8 @@ impl KeyState {
             ctrl: false,
             alt: false,
             shift: false,
+            mouse: (0, 0),
+            left_button: false,
         }
     }
 
@@ -0,0 +1,614 @@
+use caret::position::{char_at_col, display_width, grapheme_width};
+use edit::buffer::TextBuffer;
+use io::redraw::RedrawTask;
+use state::editor::Editor;
+use state::mode::{Mode, PrimitiveMode};
+
+#[cfg(feature = "orbital")]
+use orbclient::Renderer;
+
+use std::cmp::{max, min};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The cached layout of the lines of a buffer, i.e. where they are wrapped.
+pub struct LayoutCache {
+    /// The parameters (tab width, wrap width, `showbreak` width and `linebreak`), which the
+    /// lines were wrapped with.
+    params: (usize, usize, usize, bool),
+    /// The display columns, where the rows of each line wrapped so far start.
+    starts: Vec<Option<Vec<usize>>>,
+}
+
+impl Default for LayoutCache {
+    fn default() -> LayoutCache {
+        LayoutCache::new()
+    }
+}
+
+impl LayoutCache {
+    /// Create a new empty cache.
+    pub fn new() -> LayoutCache {
+        LayoutCache {
+            params: (0, 0, 0, false),
+            starts: Vec::new(),
+        }
+    }
+
+    /// Forget the layout of a changed line and the lines after it, as lines may have been
+    /// inserted or removed.
+    pub fn invalidate(&mut self, line: usize) {
+        self.starts.truncate(line);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// Where to show the line of the cursor on the screen, see `scroll_cursor_to`.
+pub enum ScrollPlace {
+    /// At the top of the screen.
+    Top,
+    /// In the middle of the screen.
+    Middle,
+    /// At the bottom of the screen.
+    Bottom,
+}
+
+/// Wrap a line to rows of `width` display columns, of which the continuation rows start with a
+/// `showbreak` columns wide marker. With `linebreak`, rows are broken after the last blank
+/// fitting on them, if any. Returns the display column, where each row starts.
+///
+/// A row is added after a full last row, for the cursor after the end of the line.
+pub fn wrap_line(
+    line: &str,
+    tabstop: usize,
+    width: usize,
+    showbreak: usize,
+    linebreak: bool,
+) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    let mut avail = width;
+    // The column after the last blank of the current row
+    let mut break_col = None;
+    let mut col = 0;
+
+    for g in line.graphemes(true) {
+        let w = grapheme_width(g, col, tabstop);
+
+        while col + w > start + avail && col > start {
+            start = match break_col {
+                Some(b) if linebreak && b > start => b,
+                _ => col,
+            };
+            starts.push(start);
+            avail = max(width.saturating_sub(showbreak), 1);
+            break_col = None;
+        }
+
+        col += w;
+        if g.chars().all(char::is_whitespace) {
+            break_col = Some(col);
+        }
+    }
+
+    if col >= start + avail && col > start {
+        starts.push(col);
+    }
+    starts
+}
+
+impl Editor {
+    /// Get the size of the text area, i.e. the window without the line numbers and the status
+    /// bar, in columns and rows.
+    #[cfg(feature = "orbital")]
+    pub fn text_area(&self) -> (usize, usize) {
+        let w = self.window.width() as usize / self.char_width;
+        let h = self.window.height() as usize / self.char_height;
+        (
+            max(w.saturating_sub(self.gutter_width()), 1),
+            max(h.saturating_sub(2), 1),
+        )
+    }
+
+    /// Get the size of the text area, i.e. the window without the line numbers and the status
+    /// bar, in columns and rows.
+    #[cfg(not(feature = "orbital"))]
+    pub fn text_area(&self) -> (usize, usize) {
+        (max(80usize.saturating_sub(self.gutter_width()), 1), 22)
+    }
+
+    /// Get the width of the `showbreak` marker, at most the width of the text area less one.
+    pub fn showbreak_width(&self) -> usize {
+        let (cols, _) = self.text_area();
+        min(self.str_option("showbreak").chars().count(), cols - 1)
+    }
+
+    /// Bring the layout cache of the current buffer up to date with the changes made to the
+    /// buffer and the options. The changed lines are remembered for the next redraw.
+    pub fn update_layout(&mut self) {
+        let (cols, _) = self.text_area();
+        let params = (
+            self.int_option("tabstop"),
+            cols,
+            self.showbreak_width(),
+            self.bool_option("linebreak"),
+        );
+        let buffer = self.buffers.current_buffer_info_mut();
+
+        if let Some(damage) = buffer.raw_buffer.take_damage() {
+            buffer.highlight.invalidate(damage);
+            buffer.layout.invalidate(damage);
+            self.screen.damage = Some(self.screen.damage.map_or(damage, |d| min(d, damage)));
+        }
+        if buffer.layout.params != params {
+            buffer.layout.params = params;
+            buffer.layout.starts.clear();
+        }
+    }
+
+    /// Get the display columns, where the wrapped rows of a line of the current buffer start.
+    pub fn row_starts(&mut self, y: usize) -> Vec<usize> {
+        self.update_layout();
+
+        let buffer = self.buffers.current_buffer_info_mut();
+        let layout = &mut buffer.layout;
+        if layout.starts.len() <= y {
+            layout.starts.resize(y + 1, None);
+        }
+        if layout.starts[y].is_none() {
+            let (tabstop, width, showbreak, linebreak) = layout.params;
+            layout.starts[y] = Some(buffer.raw_buffer.get_line(y).map_or(vec![0], |line| {
+                wrap_line(line, tabstop, width, showbreak, linebreak)
+            }));
+        }
+        layout.starts[y].clone().unwrap_or_default()
+    }
+
+    /// Get the number of screen rows a line of the current buffer is shown on.
+    pub fn line_rows(&mut self, y: usize) -> usize {
+        if self.bool_option("wrap") {
+            self.row_starts(y).len()
+        } else {
+            1
+        }
+    }
+
+    /// Get the row of a line (counted from its first row), which a display column is shown on.
+    pub fn row_of_col(&mut self, y: usize, col: usize) -> usize {
+        if self.bool_option("wrap") {
+            self.row_starts(y)
+                .iter()
+                .rposition(|&start| start <= col)
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    }
+
+    /// Get the display columns of a line shown on its `seg`th row, as a start and an end
+    /// (exclusive), and the width of the marker before them.
+    pub fn row_span(&mut self, y: usize, seg: usize) -> (usize, usize, usize) {
+        if self.bool_option("wrap") {
+            let starts = self.row_starts(y);
+            let end = starts.get(seg + 1).cloned().unwrap_or(usize::MAX);
+            let prefix = if seg > 0 { self.showbreak_width() } else { 0 };
+            (starts[seg], end, prefix)
+        } else {
+            let (cols, _) = self.text_area();
+            let scroll_x = self.buffers.current_buffer_info().scroll_x;
+            (scroll_x, scroll_x + cols, 0)
+        }
+    }
+
+    /// Get the number of screen rows of a line with text on them, i.e. without the row added for
+    /// the cursor after a full last row.
+    fn text_rows(&mut self, y: usize) -> usize {
+        let rows = self.line_rows(y);
+        if rows > 1 {
+            let width = display_width(
+                &self.buffers.current_buffer()[y],
+                self.int_option("tabstop"),
+            );
+            let (start, _, _) = self.row_span(y, rows - 1);
+            if start >= width {
+                return rows - 1;
+            }
+        }
+        rows
+    }
+
+    /// Get the position `n` screen rows below (or above, if `up`) the cursor, at the screen column
+    /// of the desired column of the cursor. Without wrapping, this moves by lines.
+    pub fn display_down(&mut self, n: usize, up: bool) -> (usize, usize) {
+        if !self.bool_option("wrap") {
+            return if up { self.up(n) } else { self.down(n) };
+        }
+
+        let (x, mut y) = self.pos();
+        let col = self.display_x((x, y));
+        let mut seg = self.row_of_col(y, col);
+
+        // The desired column, as if it was on a line of full rows
+        let (cols, _) = self.text_area();
+        let showbreak = self.showbreak_width();
+        let screen_col = match self.want_col() {
+            usize::MAX => usize::MAX,
+            want if want < cols => want,
+            want => (want - cols) % (cols - showbreak) + showbreak,
+        };
+
+        let len = self.buffers.current_buffer().len();
+        for _ in 0..n {
+            if up {
+                if seg > 0 {
+                    seg -= 1;
+                } else if y > 0 {
+                    y -= 1;
+                    seg = self.text_rows(y) - 1;
+                } else {
+                    break;
+                }
+            } else if seg + 1 < self.text_rows(y) {
+                seg += 1;
+            } else if y + 1 < len {
+                y += 1;
+                seg = 0;
+            } else {
+                break;
+            }
+        }
+
+        let (start, end, prefix) = self.row_span(y, seg);
+        let col = min(
+            start.saturating_add(screen_col.saturating_sub(prefix)),
+            end - 1,
+        );
+        let line = &self.buffers.current_buffer()[y];
+        let x = char_at_col(line, col, self.int_option("tabstop"));
+        self.bound_hor((x, y), self.tight())
+    }
+
+    /// Get the position of the first (or the last, if `end`) char on the screen row of the
+    /// cursor.
+    pub fn display_line_end(&mut self, end: bool) -> (usize, usize) {
+        let (x, y) = self.pos();
+        let col = self.display_x((x, y));
+        let seg = self.row_of_col(y, col);
+        let (start, stop, _) = self.row_span(y, seg);
+
+        let tabstop = self.int_option("tabstop");
+        let line = &self.buffers.current_buffer()[y];
+        let col = if end {
+            max(min(stop, display_width(line, tabstop)), start + 1) - 1
+        } else {
+            start
+        };
+        self.bound_hor((char_at_col(line, col, tabstop), y), true)
+    }
+
+    /// Get the line and the wrapped segment of the line shown on each row of the text area, or
+    /// `None` for rows after the end of the buffer.
+    pub fn screen_rows(&mut self) -> Vec<Option<(usize, usize)>> {
+        let (_, rows) = self.text_area();
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+        let len = self.buffers.current_buffer().len();
+
+        let mut screen = Vec::with_capacity(rows);
+        for y in scroll_y..len {
+            if screen.len() >= rows {
+                break;
+            }
+            let segments = self.line_rows(y);
+            screen.extend((0..segments).map(|seg| Some((y, seg))));
+        }
+        screen.resize(rows, None);
+        screen
+    }
+
+    /// Get the screen column and row of a position in the current buffer, if it is shown.
+    pub fn to_screen(&mut self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        let (cols, rows) = self.text_area();
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+        if y < scroll_y {
+            return None;
+        }
+
+        let col = self.display_x((x, y));
+        let seg = self.row_of_col(y, col);
+        let (start, end, prefix) = self.row_span(y, seg);
+        if col < start || col >= end {
+            return None;
+        }
+
+        let mut row = seg;
+        for line in scroll_y..y {
+            row += self.line_rows(line);
+            if row >= rows {
+                return None;
+            }
+        }
+
+        let col = col - start + prefix;
+        if row < rows && col < cols {
+            Some((col, row))
+        } else {
+            None
+        }
+    }
+
+    /// Get the position in the current buffer shown at a screen column and row, if any. Columns
+    /// after the end of a row give the last char of the row (or the end of the line).
+    pub fn from_screen(&mut self, (col, row): (usize, usize)) -> Option<(usize, usize)> {
+        let (y, seg) = (*self.screen_rows().get(row)?)?;
+        let (start, end, prefix) = self.row_span(y, seg);
+        let col = min(start + col.saturating_sub(prefix), end - 1);
+
+        let line = &self.buffers.current_buffer()[y];
+        let x = char_at_col(line, col, self.int_option("tabstop"));
+
+        Some((x, y))
+    }
+
+    /// Scroll the current buffer, such that the cursor is shown.
+    pub fn scroll_to_cursor(&mut self) {
+        let (cols, rows) = self.text_area();
+        let (x, y) = self.pos();
+        let col = self.display_x((x, y));
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+
+        // Follow the cursor horizontally, if lines are not wrapped
+        let scroll_x = if self.bool_option("wrap") {
+            0
+        } else {
+            let scroll_x = self.buffers.current_buffer_info().scroll_x;
+            let off = min(self.int_option("sidescrolloff"), (cols - 1) / 2);
+            let step = self.int_option("sidescroll");
+            let (first, last) = (scroll_x + off, scroll_x + cols - 1 - off);
+
+            if first <= col && col <= last {
+                scroll_x
+            } else if step == 0 {
+                // Put the cursor in the middle
+                col.saturating_sub(cols / 2)
+            } else if col < first {
+                scroll_x.saturating_sub(max(first - col, step))
+            } else {
+                scroll_x + max(col - last, step)
+            }
+        };
+        self.buffers.current_buffer_info_mut().scroll_x = scroll_x;
+
+        // Keep `scrolloff` lines above the cursor...
+        let off = min(self.int_option("scrolloff"), (rows - 1) / 2);
+        if y < scroll_y + off {
+            self.buffers.current_buffer_info_mut().scroll_y = y.saturating_sub(off);
+            return;
+        }
+
+        // ...and rows below it
+        let len = self.buffers.current_buffer().len();
+        let mut below = self.line_rows(y) - self.row_of_col(y, col) - 1;
+        for line in y + 1..len {
+            if below >= off {
+                break;
+            }
+            below += self.line_rows(line);
+        }
+
+        // Find the topmost line, which keeps the row of the cursor on the screen
+        let mut top = y;
+        let mut used = self.row_of_col(y, col) + 1 + min(below, off);
+        while top > scroll_y {
+            let above = self.line_rows(top - 1);
+            if used + above > rows {
+                break;
+            }
+            used += above;
+            top -= 1;
+        }
+        self.buffers.current_buffer_info_mut().scroll_y = top;
+    }
+
+    /// Get the last line of the current buffer, which is shown completely.
+    fn last_shown_line(&mut self) -> usize {
+        let (_, rows) = self.text_area();
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+        let len = self.buffers.current_buffer().len();
+
+        let mut used = 0;
+        for y in scroll_y..len {
+            used += self.line_rows(y);
+            if used > rows {
+                return max(y, scroll_y + 1) - 1;
+            }
+        }
+        len - 1
+    }
+
+    /// Scroll the current buffer `n` lines down (or up, if `up`). The cursor is moved, if it
+    /// would leave the screen.
+    pub fn scroll_by(&mut self, n: usize, up: bool) {
+        let len = self.buffers.current_buffer().len();
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+        self.buffers.current_buffer_info_mut().scroll_y = if up {
+            scroll_y.saturating_sub(n)
+        } else {
+            min(scroll_y + n, len - 1)
+        };
+        self.cursor_to_screen();
+        self.redraw_task = RedrawTask::Full;
+    }
+
+    /// Scroll the current buffer `n` lines down (or up, if `up`) and move the cursor as far, unless
+    /// the end of the buffer is shown already.
+    pub fn scroll_with_cursor(&mut self, n: usize, up: bool) {
+        let len = self.buffers.current_buffer().len();
+        let at_end = !up && self.last_shown_line() + 1 == len;
+
+        let pos = if up { self.up(n) } else { self.down(n) };
+        self.goto_vertical(pos);
+        if !at_end {
+            self.scroll_by(n, up);
+        }
+    }
+
+    /// Scroll the current buffer `n` pages down (or up, if `up`). Two lines of the previous page
+    /// are kept on the screen.
+    pub fn scroll_page(&mut self, n: usize, up: bool) {
+        let (_, rows) = self.text_area();
+        let len = self.buffers.current_buffer().len();
+
+        for _ in 0..n {
+            let scroll_y = self.buffers.current_buffer_info().scroll_y;
+            let top = if up {
+                // The second line of the page becomes the last one
+                let mut top = min(scroll_y + 2, len);
+                let mut used = 0;
+                while top > 0 {
+                    let above = self.line_rows(top - 1);
+                    if used + above > rows {
+                        break;
+                    }
+                    used += above;
+                    top -= 1;
+                }
+                min(top, scroll_y.saturating_sub(1))
+            } else {
+                // The second last line of the page becomes the first one
+                min(
+                    max(self.last_shown_line().saturating_sub(1), scroll_y + 1),
+                    len - 1,
+                )
+            };
+            self.buffers.current_buffer_info_mut().scroll_y = top;
+        }
+
+        self.cursor_to_screen();
+        self.redraw_task = RedrawTask::Full;
+    }
+
+    /// Move the cursor to the nearest line shown on the screen (keeping `scrolloff` lines from
+    /// its edges), if it is not.
+    pub fn cursor_to_screen(&mut self) {
+        let (_, rows) = self.text_area();
+        let off = min(self.int_option("scrolloff"), (rows - 1) / 2);
+        let scroll_y = self.buffers.current_buffer_info().scroll_y;
+        let last = self.last_shown_line();
+        let len = self.buffers.current_buffer().len();
+
+        // At the start and the end of the buffer, the cursor can go closer to the edges
+        let first = if scroll_y == 0 { 0 } else { scroll_y + off };
+        let last = if last + 1 == len {
+            last
+        } else {
+            last.saturating_sub(off)
+        };
+        let y = self.y();
+        let target = max(min(y, last), min(first, last));
+        if target != y {
+            let x = self.at_want_col(target);
+            self.goto_vertical((x, target));
+        }
+    }
+
+    /// Scroll the current buffer, such that the line of the cursor is shown at the top, the
+    /// middle or the bottom of the screen (keeping `scrolloff` lines from the edges).
+    pub fn scroll_cursor_to(&mut self, place: ScrollPlace) {
+        let (_, rows) = self.text_area();
+        let off = min(self.int_option("scrolloff"), (rows - 1) / 2);
+        let y = self.y();
+
+        // The screen rows to fill above the line of the cursor
+        let avail = match place {
+            ScrollPlace::Top => None,
+            ScrollPlace::Middle => Some(rows.saturating_sub(self.line_rows(y)) / 2),
+            ScrollPlace::Bottom => Some(rows.saturating_sub(self.line_rows(y) + off)),
+        };
+
+        let mut top = y;
+        match avail {
+            None => top = y.saturating_sub(off),
+            Some(avail) => {
+                let mut used = 0;
+                while top > 0 {
+                    let above = self.line_rows(top - 1);
+                    if used + above > avail {
+                        break;
+                    }
+                    used += above;
+                    top -= 1;
+                }
+            }
+        }
+        self.buffers.current_buffer_info_mut().scroll_y = top;
+        self.redraw_task = RedrawTask::Full;
+    }
+
+    /// Move the cursor to the position clicked at, given in pixels relative to the window.
+    pub fn click(&mut self, (px, py): (i32, i32)) {
+        if px < 0 || py < 0 || self.cursor().mode == Mode::Primitive(PrimitiveMode::Prompt) {
+            return;
+        }
+
+        let col = (px as usize / self.char_width).saturating_sub(self.gutter_width());
+        let row = py as usize / self.char_height;
+        if let Some(pos) = self.from_screen((col, row)) {
+            let bef = self.pos();
+            self.goto(pos);
+            self.redraw_task = RedrawTask::Cursor(bef, self.pos());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_lines_are_not_wrapped() {
+        assert_eq!(wrap_line("", 4, 10, 0, false), vec![0]);
+        assert_eq!(wrap_line("abc", 4, 10, 0, false), vec![0]);
+    }
+
+    #[test]
+    fn long_lines_wrap_at_the_width() {
+        assert_eq!(wrap_line("abcdefghijk", 4, 4, 0, false), vec![0, 4, 8]);
+        // A full last row is followed by a row for the cursor
+        assert_eq!(wrap_line("abcdefgh", 4, 4, 0, false), vec![0, 4, 8]);
+    }
+
+    #[test]
+    fn wide_chars_move_to_the_next_row_whole() {
+        // The second column of the last row is left empty
+        assert_eq!(wrap_line("abcd漢字", 4, 5, 0, false), vec![0, 4]);
+        assert_eq!(wrap_line("abc漢字", 4, 5, 0, false), vec![0, 5]);
+        assert_eq!(wrap_line("漢字漢", 4, 3, 0, false), vec![0, 2, 4]);
+        // A combining mark stays with its base char
+        assert_eq!(wrap_line("abce\u{301}f", 4, 4, 0, false), vec![0, 4]);
+    }
+
+    #[test]
+    fn continuation_rows_make_room_for_showbreak() {
+        assert_eq!(
+            wrap_line("abcdefghij", 4, 4, 2, false),
+            vec![0, 4, 6, 8, 10]
+        );
+        // A marker as wide as the row leaves at least a column for text
+        assert_eq!(wrap_line("abcdefg", 4, 4, 4, false), vec![0, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn linebreak_breaks_after_the_last_blank() {
+        assert_eq!(wrap_line("ab cd efgh", 4, 6, 0, true), vec![0, 6]);
+        assert_eq!(wrap_line("ab cdefgh", 4, 6, 0, true), vec![0, 3, 9]);
+        // Words longer than a row are still broken
+        assert_eq!(wrap_line("abcdefgh", 4, 6, 0, true), vec![0, 6]);
+        assert_eq!(wrap_line("ab cdefgh", 4, 6, 0, false), vec![0, 6]);
+    }
+
+    #[test]
+    fn tabs_fill_to_the_next_stop() {
+        assert_eq!(wrap_line("a\tb", 4, 8, 0, false), vec![0]);
+        assert_eq!(wrap_line("a\tbcdef", 4, 8, 0, false), vec![0, 8]);
+        assert_eq!(wrap_line("abcdef\tg", 4, 6, 0, false), vec![0, 6]);
+    }
+}
@@ -0,0 +1,265 @@
+use io::key::{parse_keys, Cmd};
+use state::mode::{CommandMode, Mode, PrimitiveMode};
+
+#[derive(Copy, Clone, PartialEq)]
+/// The mode a key mapping applies in.
+pub enum MapMode {
+    /// Normal mode.
+    Normal,
+    /// Insert (and replace) mode.
+    Insert,
+    /// Visual mode.
+    Visual,
+}
+
+impl MapMode {
+    /// Get the map mode of a given mode. Returns None for modes, which don't have mappings.
+    pub fn of(mode: Mode) -> Option<MapMode> {
+        match mode {
+            Mode::Command(CommandMode::Normal) => Some(MapMode::Normal),
+            Mode::Command(CommandMode::Visual(_)) => Some(MapMode::Visual),
+            Mode::Primitive(PrimitiveMode::Insert(_)) => Some(MapMode::Insert),
+            Mode::Primitive(PrimitiveMode::Prompt) => None,
+        }
+    }
+
+    /// The letter identifying the mode in mapping listings.
+    pub fn letter(self) -> char {
+        match self {
+            MapMode::Normal => 'n',
+            MapMode::Insert => 'i',
+            MapMode::Visual => 'v',
+        }
+    }
+}
+
+/// A key mapping, i.e. a key sequence, which is replaced by another key sequence.
+pub struct Mapping {
+    /// The mode the mapping applies in.
+    pub mode: MapMode,
+    /// The keys to replace.
+    pub lhs: Vec<Cmd>,
+    /// The keys to replace with.
+    pub rhs: Vec<Cmd>,
+    /// Don't remap the keys of the right hand side.
+    pub noremap: bool,
+}
+
+/// The key mappings defined by the user.
+pub struct Mappings {
+    mappings: Vec<Mapping>,
+}
+
+impl Default for Mappings {
+    fn default() -> Mappings {
+        Mappings::new()
+    }
+}
+
+impl Mappings {
+    /// Create a new empty set of mappings.
+    pub fn new() -> Mappings {
+        Mappings {
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Map `lhs` to `rhs` (both in key notation) in the given modes, replacing any existing
+    /// mapping of `lhs`.
+    pub fn map(
+        &mut self,
+        modes: &[MapMode],
+        lhs: &str,
+        rhs: &str,
+        noremap: bool,
+    ) -> Result<(), String> {
+        let lhs = parse_keys(lhs)?;
+        let rhs = parse_keys(rhs)?;
+        if lhs.is_empty() || rhs.is_empty() {
+            return Err("Empty key sequence".to_owned());
+        }
+
+        for &mode in modes {
+            self.mappings.retain(|m| !(m.mode == mode && m.lhs == lhs));
+            self.mappings.push(Mapping {
+                mode,
+                lhs: lhs.clone(),
+                rhs: rhs.clone(),
+                noremap,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Remove the mapping of `lhs` (in key notation) in the given modes.
+    pub fn unmap(&mut self, modes: &[MapMode], lhs: &str) -> Result<(), String> {
+        let lhs = parse_keys(lhs)?;
+        let len = self.mappings.len();
+
+        self.mappings
+            .retain(|m| !(modes.contains(&m.mode) && m.lhs == lhs));
+
+        if self.mappings.len() == len {
+            Err("No such mapping".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Look up the pending keys in a given mode. Returns the longest mapping, which the keys
+    /// start with, if any, and whether a longer mapping starting with all of the keys exists (in
+    /// which case more keys are needed to decide).
+    pub fn lookup(&self, mode: MapMode, keys: &[Cmd]) -> (Option<&Mapping>, bool) {
+        let mut longest: Option<&Mapping> = None;
+        let mut longer = false;
+
+        for m in self.mappings.iter().filter(|m| m.mode == mode) {
+            if m.lhs.len() > keys.len() {
+                longer |= m.lhs.starts_with(keys);
+            } else if keys.starts_with(&m.lhs) && longest.is_none_or(|l| l.lhs.len() < m.lhs.len())
+            {
+                longest = Some(m);
+            }
+        }
+
+        (longest, longer)
+    }
+
+    /// List the mappings in the given modes, whose left hand side starts with `prefix`.
+    pub fn list(&self, modes: &[MapMode], prefix: &[Cmd]) -> String {
+        let notation = |keys: &[Cmd]| keys.iter().map(|k| k.to_notation()).collect::<String>();
+
+        let descriptions: Vec<String> = self
+            .mappings
+            .iter()
+            .filter(|m| modes.contains(&m.mode) && m.lhs.starts_with(prefix))
+            .map(|m| {
+                format!(
+                    "{}\t{}\t\t{}{}",
+                    m.mode.letter(),
+                    notation(&m.lhs),
+                    if m.noremap { "* " } else { "" },
+                    notation(&m.rhs)
+                )
+            })
+            .collect();
+
+        if descriptions.is_empty() {
+            "No mapping found".to_owned()
+        } else {
+            format!(
+                "Mappings\n=====================================\n\n{}",
+                descriptions.join("\n")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::key::Key;
+    #[cfg(not(feature = "orbital"))]
+    use state::editor::Editor;
+
+    fn keys(s: &str) -> Vec<Cmd> {
+        parse_keys(s).unwrap()
+    }
+
+    #[test]
+    fn lookup_finds_the_longest_mapping() {
+        let mut maps = Mappings::new();
+        maps.map(&[MapMode::Normal], "g", "x", false).unwrap();
+        maps.map(&[MapMode::Normal], "gh", "y", false).unwrap();
+        maps.map(&[MapMode::Normal], "ghi", "z", false).unwrap();
+
+        let (m, longer) = maps.lookup(MapMode::Normal, &keys("gh"));
+        assert_eq!(m.unwrap().rhs, keys("y"));
+        assert!(longer);
+        let (m, longer) = maps.lookup(MapMode::Normal, &keys("gha"));
+        assert_eq!(m.unwrap().rhs, keys("y"));
+        assert!(!longer);
+        assert!(maps.lookup(MapMode::Insert, &keys("g")).0.is_none());
+    }
+
+    #[test]
+    fn mappings_are_replaced_and_removed_per_mode() {
+        let mut maps = Mappings::new();
+        maps.map(&[MapMode::Normal, MapMode::Visual], "Q", "gq", true)
+            .unwrap();
+        maps.map(&[MapMode::Normal], "Q", "x", false).unwrap();
+        assert_eq!(
+            maps.lookup(MapMode::Normal, &keys("Q")).0.unwrap().rhs,
+            keys("x")
+        );
+
+        maps.unmap(&[MapMode::Visual], "Q").unwrap();
+        assert!(maps.lookup(MapMode::Visual, &keys("Q")).0.is_none());
+        assert!(maps.unmap(&[MapMode::Visual], "Q").is_err());
+        assert!(maps.map(&[MapMode::Normal], "", "x", false).is_err());
+    }
+
+    #[test]
+    fn list_shows_noremap_and_notation() {
+        let mut maps = Mappings::new();
+        maps.map(&[MapMode::Insert], "jk", "<Esc>", true).unwrap();
+        assert!(maps
+            .list(&[MapMode::Insert], &[])
+            .ends_with("i\tjk\t\t* <Esc>"));
+        assert_eq!(maps.list(&[MapMode::Normal], &[]), "No mapping found");
+    }
+
+    #[cfg(not(feature = "orbital"))]
+    #[test]
+    fn get_key_expands_mappings() {
+        let mut editor = Editor::new();
+        editor
+            .mappings
+            .map(&[MapMode::Normal], "a", "bc", false)
+            .unwrap();
+        editor
+            .mappings
+            .map(&[MapMode::Normal], "b", "d", false)
+            .unwrap();
+        editor
+            .mappings
+            .map(&[MapMode::Normal], "x", "b", true)
+            .unwrap();
+
+        editor.pending_keys.extend(keys("ax"));
+        let got: Vec<Cmd> = (0..3).map(|_| editor.get_key()).collect();
+        // The right hand side of noremap mappings is not remapped
+        assert_eq!(got, keys("dcb"));
+
+        // Recursive mappings are stopped
+        editor
+            .mappings
+            .map(&[MapMode::Normal], "r", "r", false)
+            .unwrap();
+        editor.pending_keys.extend(keys("r"));
+        assert_eq!(editor.get_key().key, Key::Null);
+        assert!(editor.pending_keys.is_empty());
+    }
+
+    #[cfg(not(feature = "orbital"))]
+    #[test]
+    fn ambiguous_mappings_resolve_after_timeoutlen() {
+        let mut editor = Editor::new();
+        editor
+            .mappings
+            .map(&[MapMode::Normal], "j", "x", false)
+            .unwrap();
+        editor
+            .mappings
+            .map(&[MapMode::Normal], "jj", "y", false)
+            .unwrap();
+
+        // No more keys arrive within `timeoutlen`, so the shorter mapping is used
+        editor.pending_keys.extend(keys("j"));
+        assert_eq!(editor.get_key(), Cmd::new(Key::Char('x')));
+
+        editor.pending_keys.extend(keys("jj"));
+        assert_eq!(editor.get_key(), Cmd::new(Key::Char('y')));
+    }
+}
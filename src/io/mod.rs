@@ -1,14 +1,28 @@
+/// EditorConfig support.
+pub mod editorconfig;
 /// Loading and writing files.
 pub mod file;
 /// Graphics and rendering.
 pub mod graphics;
+/// The gutter: line numbers and signs.
+pub mod gutter;
+/// Syntax highlighting.
+pub mod highlight;
 /// Key input and parsing.
 pub mod key;
 /// The "key state" of the editor.
 ///
 /// The key state contains information about the current state of modifiers.
 pub mod key_state;
+/// The layout of wrapped lines on the screen.
+pub mod layout;
+/// User defined key mappings.
+pub mod mapping;
+/// Modelines, i.e. option settings in opened files.
+pub mod modeline;
 /// Parsing of input commands.
 pub mod parse;
 /// Partial redraws.
 pub mod redraw;
+/// Color schemes and highlight groups.
+pub mod theme;
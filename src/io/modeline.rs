@@ -0,0 +1,136 @@
+use edit::buffer::TextBuffer;
+use state::editor::Editor;
+use state::options::{set_arg_option, SetTarget};
+
+/// The markers starting a modeline, when at the start of a line or after whitespace.
+const MARKERS: &[&str] = &["sodium:", "vim:", "Vim:", "vi:"];
+
+/// The options, which modelines are allowed to set. Other options (e.g. `readonly` or
+/// `fileencoding`) are refused, as opened files are not trusted.
+const MODELINE_OPTIONS: &[&str] = &[
+    "autoindent",
+    "smartindent",
+    "textwidth",
+    "tabstop",
+    "shiftwidth",
+    "softtabstop",
+    "expandtab",
+    "filetype",
+    "fileformat",
+    "endofline",
+    "trimwhitespace",
+];
+
+/// Get the option settings of a modeline in a line, if the line has one. Two forms are
+/// supported, with any text before the marker:
+///
+/// - `vim: ts=4 sw=4 et`, where the settings are separated by whitespace or `:`.
+/// - `vim: set ts=4 sw=4 et:`, where the settings end at the next `:`, and text may follow.
+///
+/// `sodium:` can be used instead of `vim:`.
+pub fn parse_modeline(line: &str) -> Option<Vec<&str>> {
+    let rest = MARKERS.iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|&(i, _)| {
+                line[..i]
+                    .chars()
+                    .next_back()
+                    .is_none_or(char::is_whitespace)
+            })
+            .map(|(i, _)| line[i + marker.len()..].trim_start())
+    })?;
+
+    let args: Vec<&str> = if rest.starts_with("set ") || rest.starts_with("se ") {
+        let rest = &rest[rest.find(' ')? + 1..];
+        rest[..rest.find(':')?].split_whitespace().collect()
+    } else {
+        rest.split(|c: char| c == ':' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .collect()
+    };
+
+    if args.is_empty() {
+        None
+    } else {
+        Some(args)
+    }
+}
+
+impl Editor {
+    /// Apply the modelines in the first and last `modelines` lines of the current buffer to its
+    /// local options, if `modeline` is set. Errors are shown in the status bar.
+    pub fn apply_modelines(&mut self) {
+        if !self.bool_option("modeline") {
+            return;
+        }
+
+        let n = self.int_option("modelines");
+        let len = self.buffers.current_buffer().len();
+        let lines = (0..n.min(len)).chain(len.saturating_sub(n).max(n)..len);
+
+        let args: Vec<String> = lines
+            .filter_map(|y| parse_modeline(&self.buffers.current_buffer()[y]))
+            .flatten()
+            .map(ToOwned::to_owned)
+            .collect();
+
+        let mut errors = Vec::new();
+        for arg in args {
+            let res = match set_arg_option(&arg) {
+                Ok(def) if MODELINE_OPTIONS.contains(&def.name) => self
+                    .apply_option(&arg, SetTarget::Local)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                Ok(def) => Err(format!("Option {} is not allowed in modelines", def.name)),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(msg) = res {
+                errors.push(msg);
+            }
+        }
+
+        if !errors.is_empty() {
+            self.status_bar.msg = format!("Modeline: {}", errors.join("; "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_form_takes_every_argument() {
+        assert_eq!(
+            parse_modeline("// vim: ts=4 sw=4 et"),
+            Some(vec!["ts=4", "sw=4", "et"])
+        );
+        assert_eq!(parse_modeline("vim:ts=8:noet"), Some(vec!["ts=8", "noet"]));
+        assert_eq!(parse_modeline("# vi: tw=72"), Some(vec!["tw=72"]));
+        assert_eq!(parse_modeline("sodium: ai"), Some(vec!["ai"]));
+    }
+
+    #[test]
+    fn set_form_ends_at_a_colon() {
+        assert_eq!(
+            parse_modeline("/* vim: set ts=2 sw=2: */"),
+            Some(vec!["ts=2", "sw=2"])
+        );
+        assert_eq!(
+            parse_modeline("# vi: se tw=60 :trailing"),
+            Some(vec!["tw=60"])
+        );
+        // Without the closing colon, the set form is invalid
+        assert_eq!(parse_modeline("vim: set ts=2"), None);
+    }
+
+    #[test]
+    fn marker_must_start_a_word() {
+        assert_eq!(parse_modeline("novim: ts=4"), None);
+        assert_eq!(parse_modeline("see http://vi: ts=4"), None);
+        assert_eq!(parse_modeline("no modeline here"), None);
+        assert_eq!(parse_modeline("vim:"), None);
+        // A later marker starting a word is still found
+        assert_eq!(parse_modeline("novim: x vim: ts=4"), Some(vec!["ts=4"]));
+    }
+}
@@ -1,12 +1,20 @@
 use io::key::{Cmd, Key};
-#[cfg(feature = "orbital")]
+use io::mapping::MapMode;
 use io::redraw::RedrawTask;
 use state::editor::Editor;
-#[cfg(feature = "orbital")]
 use state::mode::Mode;
 
+use std::time::Duration;
+
 #[cfg(feature = "orbital")]
 use orbclient::EventOption;
+#[cfg(feature = "orbital")]
+use std::thread;
+#[cfg(feature = "orbital")]
+use std::time::Instant;
+
+/// The maximal number of mapping expansions for a single key, to catch recursive mappings.
+const MAX_EXPANSIONS: usize = 1000;
 
 #[derive(Copy, Clone)]
 /// An instruction, i.e. a command and a numeral parameter
@@ -36,101 +44,226 @@ impl Parameter {
     pub fn d(self) -> usize {
         self.or(1)
     }
+    /// Is the numeral given?
+    #[inline]
+    pub fn is_int(self) -> bool {
+        matches!(self, Parameter::Int(_))
+    }
 }
 
 impl Editor {
-    /// Get the next character input. Useful for commands taking a character as post-parameter,
-    /// such as r (replace).
-    pub fn get_char(&mut self) -> char {
-        #[cfg(feature = "orbital")]
+    /// Get the next key input, together with its modifiers, after applying the key mappings of
+    /// the current mode. Keys pushed back to the input queue are returned first.
+    pub fn get_key(&mut self) -> Cmd {
+        let mut expansions = 0;
+
         loop {
-            for event in self.window.events() {
-                match event.to_option() {
-                    EventOption::Key(k) => {
-                        if let Some(Key::Char(c)) = self.key_state.feed(k) {
-                            self.status_bar.cmd.push(c);
-                            self.redraw_task = RedrawTask::StatusBar;
-                            return c;
-                        }
+            if self.pending_keys.is_empty() {
+                self.read_keys(None);
+            }
+            if self.noremap_keys > 0 {
+                break;
+            }
+
+            let mode = match MapMode::of(self.cursor().mode) {
+                Some(mode) => mode,
+                None => break,
+            };
+
+            let keys: Vec<Cmd> = self.pending_keys.iter().cloned().collect();
+            let (mapping, longer) = self.mappings.lookup(mode, &keys);
+            let mapping = mapping.map(|m| (m.lhs.len(), m.rhs.clone(), m.noremap));
+
+            // Wait for more keys, if they might complete a longer mapping
+            let timeout = Duration::from_millis(self.int_option("timeoutlen") as u64);
+            if longer && self.read_keys(Some(timeout)) {
+                continue;
+            }
+
+            match mapping {
+                Some((len, rhs, noremap)) => {
+                    expansions += 1;
+                    if expansions > MAX_EXPANSIONS {
+                        self.status_bar.msg = "Recursive mapping".to_owned();
+                        self.pending_keys.clear();
+                        self.noremap_keys = 0;
+                        return Cmd::new(Key::Null);
+                    }
+
+                    self.pending_keys.drain(..len);
+                    for &k in rhs.iter().rev() {
+                        self.pending_keys.push_front(k);
+                    }
+                    if noremap {
+                        self.noremap_keys = rhs.len();
                     }
-                    _ => {}
                 }
+                None => break,
             }
         }
-        #[cfg(not(feature = "orbital"))]
-        '\0'
+
+        self.pop_key()
     }
 
-    /// Get the next instruction, i.e. the next input of a command together with a numeral
-    /// parameter.
-    pub fn get_inst(&mut self) -> Inst {
+    /// Get the next key input, without applying any key mappings.
+    pub fn get_key_noremap(&mut self) -> Cmd {
+        if self.pending_keys.is_empty() {
+            self.read_keys(None);
+        }
+
+        self.pop_key()
+    }
+
+    /// Pop the first key from the input queue.
+    fn pop_key(&mut self) -> Cmd {
+        if self.noremap_keys > 0 {
+            self.noremap_keys -= 1;
+        }
+
+        self.pending_keys
+            .pop_front()
+            .unwrap_or_else(|| Cmd::new(Key::Null))
+    }
+
+    /// Read the keys of the pending window events to the end of the input queue. Blocks until a
+    /// key is read, or the timeout (if any) has elapsed. Returns whether any key was read.
+    #[cfg_attr(not(feature = "orbital"), allow(unused_variables))]
+    pub fn read_keys(&mut self, timeout: Option<Duration>) -> bool {
+        let len = self.pending_keys.len();
+
         #[cfg(feature = "orbital")]
         {
-            let mut n = 0;
-            let mut unset = true;
-
-            let mut key = Key::Null;
-            self.status_bar.cmd = String::new();
+            let start = Instant::now();
+            self.window.set_async(timeout.is_some());
 
-            // self.status_bar.cmd = String::new();
-            loop {
+            while self.pending_keys.len() == len {
                 for event in self.window.events() {
                     match event.to_option() {
                         EventOption::Key(key_event) => {
                             if let Some(k) = self.key_state.feed(key_event) {
-                                let c = k.to_char();
-                                self.status_bar.cmd.push(c);
-                                self.redraw_status_bar();
-
-                                match self.cursor().mode {
-                                    Mode::Primitive(_) => {
-                                        key = k;
-                                    }
-                                    Mode::Command(_) => {
-                                        n = match c {
-                                            '0'..='9' => if unset && c == '0' {
-                                                key = k;
-                                                n
-                                            } else {
-                                                unset = false;
-                                                n * 10 + ((c as u8) - b'0') as usize
-                                            }
-                                            
-                                            _ => {
-                                                key = k;
-                                                n
-                                            }
-                                        };
-                                    }
-                                }
-                            }
-                            match key {
-                                Key::Null => {}
-                                _ => {
-                                    return Inst(
-                                        if unset {
-                                            Parameter::Null
-                                        } else {
-                                            Parameter::Int(n)
-                                        },
-                                        Cmd { key: key },
-                                    );
-                                }
+                                let cmd = Cmd::from_state(k, &self.key_state);
+                                self.pending_keys.push_back(cmd);
                             }
                         }
                         EventOption::Quit(_) => {
-                            return Inst(Parameter::Null, Cmd { key: Key::Quit });
+                            self.pending_keys.push_back(Cmd::new(Key::Quit));
                         }
                         EventOption::Resize(_) => {
                             self.redraw_task = RedrawTask::Full;
                             self.redraw();
                         }
+                        EventOption::Mouse(mouse_event) => {
+                            self.key_state.mouse = (mouse_event.x, mouse_event.y);
+                        }
+                        EventOption::Button(button_event) => {
+                            // Move the cursor when the left button is pressed
+                            if button_event.left && !self.key_state.left_button {
+                                self.click(self.key_state.mouse);
+                                self.redraw();
+                            }
+                            self.key_state.left_button = button_event.left;
+                        }
                         _ => {}
                     }
                 }
+
+                if let Some(timeout) = timeout {
+                    if start.elapsed() >= timeout {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+
+            self.window.set_async(false);
+        }
+
+        self.pending_keys.len() > len
+    }
+
+    /// Push keys back to the front of the input queue, such that they are read again by the
+    /// next calls to `get_key`. The keys are not remapped again.
+    pub fn unread_keys(&mut self, keys: &[Cmd]) {
+        for &k in keys.iter().rev() {
+            self.pending_keys.push_front(k);
+        }
+        self.noremap_keys += keys.len();
+    }
+
+    /// Push an instruction back to the front of the input queue.
+    pub fn unread_inst(&mut self, Inst(para, cmd): Inst) {
+        self.unread_keys(&[cmd]);
+        if let Parameter::Int(n) = para {
+            let digits: Vec<Cmd> = n
+                .to_string()
+                .chars()
+                .map(|c| Cmd::new(Key::Char(c)))
+                .collect();
+            self.unread_keys(&digits);
+        }
+    }
+
+    /// Get the next character input. Useful for commands taking a character as post-parameter,
+    /// such as r (replace).
+    pub fn get_char(&mut self) -> char {
+        loop {
+            let cmd = self.get_key_noremap();
+            match cmd.key {
+                Key::Char(c) => {
+                    self.status_bar.cmd.push(c);
+                    self.redraw_task = RedrawTask::StatusBar;
+                    return c;
+                }
+                Key::Quit => {
+                    // Leave the quit request to the main loop
+                    self.unread_keys(&[cmd]);
+                    return '\0';
+                }
+                Key::Null => return '\0',
+                _ => {}
+            }
+        }
+    }
+
+    /// Get the next instruction, i.e. the next input of a command together with a numeral
+    /// parameter.
+    pub fn get_inst(&mut self) -> Inst {
+        let mut n = 0;
+        let mut unset = true;
+
+        self.status_bar.cmd = String::new();
+
+        loop {
+            let cmd = self.get_key();
+            let c = cmd.key.to_char();
+
+            if let Key::Null = cmd.key {
+                return Inst(Parameter::Null, cmd);
+            }
+
+            self.status_bar.cmd.push(c);
+            self.redraw_status_bar();
+
+            let counting = match self.cursor().mode {
+                Mode::Primitive(_) => false,
+                Mode::Command(_) => {
+                    !cmd.ctrl && !cmd.alt && c.is_ascii_digit() && (!unset || c != '0')
+                }
+            };
+
+            if counting {
+                unset = false;
+                n = n * 10 + ((c as u8) - b'0') as usize;
+            } else {
+                return Inst(
+                    if unset {
+                        Parameter::Null
+                    } else {
+                        Parameter::Int(n)
+                    },
+                    cmd,
+                );
             }
         }
-        #[cfg(not(feature = "orbital"))]
-        Inst(Parameter::Null, Cmd { key: Key::Null })
     }
 }
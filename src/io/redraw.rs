@@ -1,3 +1,5 @@
+use io::theme::Group;
+
 use std::ops::Range;
 
 #[derive(Clone)]
@@ -16,3 +18,91 @@ pub enum RedrawTask {
     /// Move cursor.
     Cursor((usize, usize), (usize, usize)),
 }
+
+impl RedrawTask {
+    /// The lines, which the task requires to be redrawn, if any.
+    pub fn lines(&self) -> Vec<Range<usize>> {
+        let mut lines = Vec::new();
+        match *self {
+            RedrawTask::None | RedrawTask::Full | RedrawTask::StatusBar => {}
+            RedrawTask::Lines(ref range) => lines.push(range.clone()),
+            RedrawTask::LinesAfter(y) => lines.push(y..usize::MAX),
+            RedrawTask::Cursor((_, from), (_, to)) => {
+                lines.push(from..from + 1);
+                lines.push(to..to + 1);
+            }
+        }
+        lines
+    }
+}
+
+#[derive(Clone, PartialEq)]
+/// The parameters the screen was drawn with. If any of them changes, every row is redrawn.
+pub struct ScreenParams {
+    /// The size of the window in pixels.
+    pub size: (usize, usize),
+    /// The index of the buffer shown.
+    pub buffer: usize,
+    /// The first line shown.
+    pub scroll_y: usize,
+    /// The first display column shown, if lines are not wrapped.
+    pub scroll_x: usize,
+    /// Whether lines are wrapped, at blanks (`linebreak`), with a marker (`showbreak`).
+    pub wrap: (bool, bool, String),
+    /// The width of the gutter.
+    pub gutter: usize,
+    /// Whether line numbers are shown relative to the cursor (`relativenumber`), and the absolute
+    /// number of the cursor line with them (`line_numbers`).
+    pub numbers: (bool, bool),
+    /// The signs in the sign column.
+    pub signs: Vec<(usize, char, Group)>,
+    /// The tab width.
+    pub tabstop: usize,
+    /// Whether the prompt is open (covering the last row).
+    pub prompt: bool,
+    /// The filetype, which the lines are highlighted by.
+    pub filetype: String,
+    /// Whether syntax highlighting is enabled.
+    pub highlight: bool,
+    /// Whether the line of the cursor is marked.
+    pub line_marker: bool,
+    /// The search pattern, whose matches are highlighted.
+    pub search: Option<String>,
+}
+
+/// The state of the screen as last drawn, so that only the rows, which changed, are redrawn.
+pub struct Screen {
+    /// The parameters of the last frame, or `None` if nothing was drawn yet.
+    pub params: Option<ScreenParams>,
+    /// The line and wrapped segment of the line shown on each screen row, or `None` for rows
+    /// after the end of the buffer.
+    pub rows: Vec<Option<(usize, usize)>>,
+    /// A hash of what was drawn on each screen row.
+    pub hashes: Vec<u64>,
+    /// The lines of the cursors.
+    pub cursor_lines: Vec<usize>,
+    /// The first and last line of the visual selection, if any.
+    pub selection: Option<(usize, usize)>,
+    /// The first line of the buffer changed since the last frame, if any.
+    pub damage: Option<usize>,
+}
+
+impl Default for Screen {
+    fn default() -> Screen {
+        Screen::new()
+    }
+}
+
+impl Screen {
+    /// Create the state of a screen, on which nothing is drawn yet.
+    pub fn new() -> Screen {
+        Screen {
+            params: None,
+            rows: Vec::new(),
+            hashes: Vec::new(),
+            cursor_lines: Vec::new(),
+            selection: None,
+            damage: None,
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use core::config::config_dir;
+use edit::buffer::{SplitBuffer, TextBuffer};
+use io::redraw::RedrawTask;
+use state::editor::{Buffer, Editor};
+
+use std::fs::File;
+use std::io::Read;
+
+/// A color, as its red, green and blue components.
+pub type Rgb = (u8, u8, u8);
+
+/// The foreground used, when neither a group nor `Normal` has one.
+const FALLBACK_FG: Rgb = (255, 255, 255);
+/// The background used, when neither a group nor `Normal` has one.
+const FALLBACK_BG: Rgb = (25, 25, 25);
+
+/// The bundled color schemes, by name.
+pub const THEMES: &[(&str, &str)] = &[
+    ("default", include_str!("../../themes/default.theme")),
+    ("light", include_str!("../../themes/light.theme")),
+    ("ocean", include_str!("../../themes/ocean.theme")),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+/// A highlight group, i.e. a kind of drawn element, which is colored by the theme.
+pub enum Group {
+    /// Text and the background of the window.
+    Normal,
+    /// The background of the line of the cursor.
+    CursorLine,
+    /// The cursor.
+    Cursor,
+    /// The cursors, which are not currently active.
+    InactiveCursor,
+    /// Line numbers.
+    LineNr,
+    /// The line number of the line of the cursor.
+    CursorLineNr,
+    /// The sign column.
+    SignColumn,
+    /// Signs of marks.
+    MarkSign,
+    /// Signs of changed lines.
+    ChangeSign,
+    /// Markers of wrapped and truncated lines.
+    NonText,
+    /// The status bar.
+    StatusLine,
+    /// The text of the prompt.
+    Prompt,
+    /// The visual selection.
+    Visual,
+    /// Matches of the last search pattern.
+    Search,
+    /// Keywords.
+    Keyword,
+    /// Types.
+    Type,
+    /// Comments.
+    Comment,
+    /// String and char literals.
+    String,
+    /// Numbers.
+    Number,
+    /// Lifetimes.
+    Lifetime,
+    /// Attributes.
+    Attribute,
+    /// Headings.
+    Heading,
+    /// Operators.
+    Operator,
+    /// Punctuation.
+    Punctuation,
+    /// Delimiters.
+    Delimiter,
+}
+
+/// Every highlight group, in order.
+pub const GROUPS: &[Group] = &[
+    Group::Normal,
+    Group::CursorLine,
+    Group::Cursor,
+    Group::InactiveCursor,
+    Group::LineNr,
+    Group::CursorLineNr,
+    Group::SignColumn,
+    Group::MarkSign,
+    Group::ChangeSign,
+    Group::NonText,
+    Group::StatusLine,
+    Group::Prompt,
+    Group::Visual,
+    Group::Search,
+    Group::Keyword,
+    Group::Type,
+    Group::Comment,
+    Group::String,
+    Group::Number,
+    Group::Lifetime,
+    Group::Attribute,
+    Group::Heading,
+    Group::Operator,
+    Group::Punctuation,
+    Group::Delimiter,
+];
+
+impl Group {
+    /// The name of the group, as used in theme files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Group::Normal => "Normal",
+            Group::CursorLine => "CursorLine",
+            Group::Cursor => "Cursor",
+            Group::InactiveCursor => "InactiveCursor",
+            Group::LineNr => "LineNr",
+            Group::CursorLineNr => "CursorLineNr",
+            Group::SignColumn => "SignColumn",
+            Group::MarkSign => "MarkSign",
+            Group::ChangeSign => "ChangeSign",
+            Group::NonText => "NonText",
+            Group::StatusLine => "StatusLine",
+            Group::Prompt => "Prompt",
+            Group::Visual => "Visual",
+            Group::Search => "Search",
+            Group::Keyword => "Keyword",
+            Group::Type => "Type",
+            Group::Comment => "Comment",
+            Group::String => "String",
+            Group::Number => "Number",
+            Group::Lifetime => "Lifetime",
+            Group::Attribute => "Attribute",
+            Group::Heading => "Heading",
+            Group::Operator => "Operator",
+            Group::Punctuation => "Punctuation",
+            Group::Delimiter => "Delimiter",
+        }
+    }
+
+    /// Get a group by its name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Group> {
+        GROUPS
+            .iter()
+            .find(|g| g.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// The colors of a highlight group. Unset colors fall back to those of `Normal`.
+pub struct Style {
+    /// The foreground (text) color.
+    pub fg: Option<Rgb>,
+    /// The background color.
+    pub bg: Option<Rgb>,
+}
+
+/// A color scheme: The colors of every highlight group.
+pub struct Theme {
+    /// The name of the color scheme.
+    pub name: String,
+    /// The style of each group, indexed as in `GROUPS`.
+    styles: Vec<Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::new()
+    }
+}
+
+impl Theme {
+    /// Create a new theme, with no colors set.
+    pub fn new() -> Theme {
+        Theme {
+            name: String::new(),
+            styles: vec![Style { fg: None, bg: None }; GROUPS.len()],
+        }
+    }
+
+    /// Get the style of a group, as set by the theme.
+    pub fn style(&self, group: Group) -> Style {
+        self.styles[group as usize]
+    }
+
+    /// Set the style of a group.
+    pub fn set_style(&mut self, group: Group, style: Style) {
+        self.styles[group as usize] = style;
+    }
+
+    /// Get the foreground color of a group.
+    pub fn fg(&self, group: Group) -> Rgb {
+        self.style(group)
+            .fg
+            .or(self.style(Group::Normal).fg)
+            .unwrap_or(FALLBACK_FG)
+    }
+
+    /// Get the background color of a group.
+    pub fn bg(&self, group: Group) -> Rgb {
+        self.style(group)
+            .bg
+            .or(self.style(Group::Normal).bg)
+            .unwrap_or(FALLBACK_BG)
+    }
+
+    /// Describe the style of a group, in the syntax of the `hi` command.
+    pub fn describe(&self, group: Group) -> String {
+        let style = self.style(group);
+        let color = |c: Option<Rgb>| match c {
+            Some((r, g, b)) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            None => "NONE".to_owned(),
+        };
+        format!(
+            "{} fg={} bg={}",
+            group.name(),
+            color(style.fg),
+            color(style.bg)
+        )
+    }
+}
+
+/// Parse a color, either `#rrggbb` or `NONE` (which unsets it).
+pub fn parse_color(s: &str) -> Result<Option<Rgb>, String> {
+    if s.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+
+    let hex = match s.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => hex,
+        _ => return Err(format!("Invalid color: {}", s)),
+    };
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+    Ok(Some((component(0), component(2), component(4))))
+}
+
+impl Editor {
+    /// Set or show the colors of a highlight group, given as `Group [fg=color] [bg=color]`.
+    /// Colors not given are kept.
+    pub fn highlight_group(&mut self, args: &str) -> Result<(), String> {
+        let mut args = args.split_whitespace();
+        let name = args.next().unwrap_or("");
+        let group =
+            Group::from_name(name).ok_or_else(|| format!("Unknown highlight group: {}", name))?;
+
+        let mut style = self.theme.style(group);
+        let mut changed = false;
+        for arg in args {
+            match arg.find('=').map(|i| (&arg[..i], &arg[i + 1..])) {
+                Some(("fg", color)) => style.fg = parse_color(color)?,
+                Some(("bg", color)) => style.bg = parse_color(color)?,
+                _ => return Err(format!("Invalid argument: {}", arg)),
+            }
+            changed = true;
+        }
+
+        if changed {
+            self.theme.set_style(group, style);
+            self.redraw_task = RedrawTask::Full;
+        } else {
+            self.status_bar.msg = self.theme.describe(group);
+        }
+        Ok(())
+    }
+
+    /// List the highlight groups and their colors in a new buffer.
+    pub fn list_highlight_groups(&mut self) {
+        let description = GROUPS
+            .iter()
+            .map(|&g| format!("hi {}", self.theme.describe(g)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut new_buffer: Buffer = SplitBuffer::from_str(&description).into();
+        new_buffer.title = Some("<Highlight>".into());
+        new_buffer.is_transient = true; // delete the buffer when the user switches away
+
+        let new_buffer_index = self.buffers.new_buffer(new_buffer);
+        self.buffers.switch_to(new_buffer_index);
+        self.redraw_task = RedrawTask::Full;
+    }
+
+    /// Load a color scheme by name. A theme file is a list of prompt commands (usually `hi`),
+    /// which are applied on top of no colors at all. Theme files in the `themes` directory of
+    /// the configuration directory (`~/.config/sodium/themes/<name>.theme`) take precedence
+    /// over the bundled themes.
+    pub fn colorscheme(&mut self, name: &str) -> Result<(), String> {
+        let user_theme = config_dir().and_then(|dir| {
+            let mut con = String::new();
+            File::open(format!("{}/themes/{}.theme", dir, name))
+                .and_then(|mut file| file.read_to_string(&mut con))
+                .ok()
+                .map(|_| con)
+        });
+        let con = match user_theme {
+            Some(con) => con,
+            None => match THEMES.iter().find(|&&(n, _)| n == name) {
+                Some(&(_, con)) => con.to_owned(),
+                None => return Err(format!("Unknown color scheme: {}", name)),
+            },
+        };
+
+        self.theme = Theme::new();
+        self.theme.name = name.to_owned();
+        self.redraw_task = RedrawTask::Full;
+        self.source_str(name, &con)
+    }
+}
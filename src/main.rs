@@ -1,10 +1,11 @@
 //! Sodium is a next generation Vi-like editor.
 
-#![feature(stmt_expr_attributes)]
 #![deny(missing_docs)]
 
 #[cfg(feature = "orbital")]
 extern crate orbclient;
+extern crate unicode_segmentation;
+extern crate unicode_width;
 
 /// Core functionality.
 #[macro_use]
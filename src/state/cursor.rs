@@ -1,3 +1,4 @@
+use edit::buffer::grapheme_chars;
 use state::editor::Editor;
 use state::mode::{CommandMode, Mode};
 
@@ -11,6 +12,11 @@ pub struct Cursor {
     pub y: usize,
     /// The mode of the cursor
     pub mode: Mode,
+    /// The position where the visual selection started
+    pub anchor: (usize, usize),
+    /// The display column vertical motions keep the cursor at, if it is not the column of the
+    /// cursor (e.g. after passing a shorter line). `usize::MAX` keeps it at the end of lines.
+    pub want_col: Option<usize>,
 }
 
 impl Cursor {
@@ -20,6 +26,8 @@ impl Cursor {
             x: 0,
             y: 0,
             mode: Mode::Command(CommandMode::Normal),
+            anchor: (0, 0),
+            want_col: None,
         }
     }
 }
@@ -29,10 +37,7 @@ impl Editor {
     #[inline]
     pub fn current(&self) -> Option<char> {
         let (x, y) = self.pos();
-        match self.buffers.current_buffer()[y].chars().nth(x) {
-            Some(c) => Some(c),
-            None => None,
-        }
+        grapheme_chars(&self.buffers.current_buffer()[y]).nth(x)
     }
 
     /// Get the current cursor
@@ -1,11 +1,20 @@
+use core::commands::default_registry;
+use core::registry::Registry;
 use edit::buffer::{SplitBuffer, TextBuffer};
+use edit::register::Register;
 use io::graphics::StatusBar;
+use io::highlight::HighlightCache;
 use io::key::{Cmd, Key};
 use io::key_state::KeyState;
+use io::layout::LayoutCache;
+use io::mapping::Mappings;
 use io::parse::Inst;
-use io::redraw::RedrawTask;
+use io::redraw::{RedrawTask, Screen};
+use io::theme::Theme;
 use state::cursor::Cursor;
-use state::options::Options;
+use state::mark::{FileMark, PositionList};
+use state::options::{LocalOptions, Options};
+use std::collections::{HashMap, VecDeque};
 use std::slice::Iter;
 
 #[cfg(feature = "orbital")]
@@ -36,6 +45,18 @@ pub struct Buffer {
     pub is_transient: bool,
     /// True if the buffer has unsaved changes
     pub dirty: bool,
+    /// The buffer-local marks
+    pub marks: HashMap<char, (usize, usize)>,
+    /// The jump list
+    pub jumps: PositionList,
+    /// The change list
+    pub changes: PositionList,
+    /// The options local to the buffer
+    pub options: LocalOptions,
+    /// The cached highlighting state
+    pub highlight: HighlightCache,
+    /// The cached layout of the lines
+    pub layout: LayoutCache,
 }
 
 impl Buffer {
@@ -50,6 +71,12 @@ impl Buffer {
             title: None,
             is_transient: false,
             dirty: false,
+            marks: HashMap::new(),
+            jumps: PositionList::new(),
+            changes: PositionList::new(),
+            options: LocalOptions::new(),
+            highlight: HighlightCache::new(),
+            layout: LayoutCache::new(),
         }
     }
 }
@@ -190,19 +217,45 @@ pub struct Editor {
     pub char_height: usize,
     /// The files currently open
     pub files: Vec<String>,
+    /// The global marks
+    pub global_marks: HashMap<char, FileMark>,
+    /// The last search pattern
+    pub last_search: Option<String>,
+    /// The register, containing the last yanked or deleted text
+    pub register: Option<Register>,
+    /// The lines to filter with the next filter command
+    pub filter_range: Option<(usize, usize)>,
+    /// The command definitions
+    pub commands: Registry,
+    /// Keys read (or pushed back), but not yet consumed
+    pub pending_keys: VecDeque<Cmd>,
+    /// The number of keys at the front of `pending_keys`, which must not be remapped
+    pub noremap_keys: usize,
+    /// The key mappings
+    pub mappings: Mappings,
+    /// The nesting of the files currently being sourced
+    pub source_depth: usize,
+    /// The commands to invoke when the filetype of a buffer is set, by filetype pattern
+    pub filetype_hooks: Vec<(String, String)>,
+    /// The color scheme
+    pub theme: Theme,
+    /// The state of the screen as last drawn
+    pub screen: Screen,
 }
 
-impl Editor {
-    /// Create new default state editor
-    pub fn init() {
-        #[cfg(feature = "orbital")]
-        let window =
-            Window::new_flags(-1, -1, 700, 500, &"Sodium", &[WindowFlag::Resizable]).unwrap();
+#[cfg(not(feature = "orbital"))]
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new()
+    }
+}
 
-        #[cfg(feature = "orbital")]
-        let mut editor = Editor {
+impl Editor {
+    /// Create an editor without a window, holding a single empty buffer.
+    #[cfg(not(feature = "orbital"))]
+    pub fn new() -> Editor {
+        Editor {
             buffers: BufferManager::new(),
-            window: window,
             status_bar: StatusBar::new(),
             prompt: vec![String::new()],
             prompt_index: 0,
@@ -213,11 +266,31 @@ impl Editor {
             char_width: 8,
             char_height: 16,
             files: Vec::new(),
-        };
+            global_marks: HashMap::new(),
+            last_search: None,
+            register: None,
+            filter_range: None,
+            commands: default_registry(),
+            pending_keys: VecDeque::new(),
+            noremap_keys: 0,
+            mappings: Mappings::new(),
+            source_depth: 0,
+            filetype_hooks: Vec::new(),
+            theme: Theme::new(),
+            screen: Screen::new(),
+        }
+    }
 
-        #[cfg(not(feature = "orbital"))]
+    /// Create new default state editor
+    pub fn init() {
+        #[cfg(feature = "orbital")]
+        let window =
+            Window::new_flags(-1, -1, 700, 500, &"Sodium", &[WindowFlag::Resizable]).unwrap();
+
+        #[cfg(feature = "orbital")]
         let mut editor = Editor {
             buffers: BufferManager::new(),
+            window: window,
             status_bar: StatusBar::new(),
             prompt: vec![String::new()],
             prompt_index: 0,
@@ -228,9 +301,25 @@ impl Editor {
             char_width: 8,
             char_height: 16,
             files: Vec::new(),
+            global_marks: HashMap::new(),
+            last_search: None,
+            register: None,
+            filter_range: None,
+            commands: default_registry(),
+            pending_keys: VecDeque::new(),
+            noremap_keys: 0,
+            mappings: Mappings::new(),
+            source_depth: 0,
+            filetype_hooks: Vec::new(),
+            theme: Theme::new(),
+            screen: Screen::new(),
         };
 
+        #[cfg(not(feature = "orbital"))]
+        let mut editor = Editor::new();
+
         let mut files: Vec<String> = Vec::new();
+        let mut config_path: Option<String> = None;
 
         let mut args_iter = args().skip(1).peekable();
         loop {
@@ -252,19 +341,16 @@ impl Editor {
                     return;
                 }
                 "-u" => {
-                    unimplemented!();
-                    /*
-                    match args_iter.peek() {
+                    match args_iter.next() {
                         Some(config) => {
                             // this is the config file to use for this session
-                            println!("{}", config);
-                        },
+                            config_path = Some(config);
+                        }
                         None => {
-                            panic!("No config file specified.");
+                            println!("No config file specified.");
+                            return;
                         }
                     }
-                    args_iter.next();
-                    */
                 }
                 "--" => {
                     // everything from here on out is a file
@@ -304,6 +390,9 @@ impl Editor {
             }
         }
 
+        let _ = editor.colorscheme("default"); // The bundled theme is valid
+        editor.load_config(config_path);
+
         if files.len() > 0 {
             // TODO: open multiple files into separate buffers
             editor.open(&files[0]);
@@ -317,7 +406,7 @@ impl Editor {
 
         loop {
             let inp = editor.get_inst();
-            if let Inst(_, Cmd { key: Key::Quit }) = inp {
+            if inp.1.key == Key::Quit {
                 debugln!(editor, "C'ya");
                 break;
             }
@@ -0,0 +1,431 @@
+use core::config::MAX_SOURCE_DEPTH;
+use core::prompt::PromptCommand;
+use edit::buffer::TextBuffer;
+use state::editor::Editor;
+use state::options::{OptionValue, SetTarget};
+
+use std::path::Path;
+
+/// The highlighting definition of a filetype.
+pub struct Syntax {
+    /// The keywords of the language.
+    pub keywords: &'static [&'static str],
+    /// The starts of comments running to the end of the line.
+    pub line_comments: &'static [&'static str],
+    /// The start and the end of block comments, if any.
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Whether block comments nest.
+    pub nested_comments: bool,
+    /// The chars delimiting strings.
+    pub strings: &'static [char],
+    /// The char escaping the following char in strings, if any.
+    pub escape: Option<char>,
+    /// Whether strings can span multiple lines.
+    pub multiline_strings: bool,
+    /// Whether `'` starts a lifetime (e.g. `'a`), where it doesn't delimit a char literal.
+    pub lifetimes: bool,
+    /// The starts of attributes, which run to the matching `]`.
+    pub attributes: &'static [&'static str],
+    /// The starts of lines, which are headings.
+    pub headings: &'static [&'static str],
+    /// The line starting and ending a block of code (e.g. in Markdown), if any.
+    pub code_fence: Option<&'static str>,
+    /// Whether numbers, operators and delimiters are highlighted.
+    pub symbols: bool,
+    /// Whether capitalized identifiers are highlighted as types.
+    pub capitalized_types: bool,
+}
+
+/// The highlighting of files of unknown type: Strings, numbers and symbols.
+pub const PLAIN_SYNTAX: Syntax = Syntax {
+    keywords: &[],
+    line_comments: &[],
+    block_comment: None,
+    nested_comments: false,
+    strings: &['"', '\''],
+    escape: Some('\\'),
+    multiline_strings: false,
+    lifetimes: false,
+    attributes: &[],
+    headings: &[],
+    code_fence: None,
+    symbols: true,
+    capitalized_types: false,
+};
+
+/// The highlighting of shell-like languages.
+const SHELL_SYNTAX: Syntax = Syntax {
+    line_comments: &["#"],
+    multiline_strings: true,
+    ..PLAIN_SYNTAX
+};
+
+/// The definition of a filetype.
+pub struct FiletypeDef {
+    /// The name of the filetype, as used in the `filetype` option.
+    pub name: &'static str,
+    /// The extensions of files of this type (without the dot).
+    pub extensions: &'static [&'static str],
+    /// Complete file names of files of this type.
+    pub file_names: &'static [&'static str],
+    /// The interpreters in shebang lines of files of this type.
+    pub interpreters: &'static [&'static str],
+    /// Prefixes of the first line identifying files of this type.
+    pub first_line: &'static [&'static str],
+    /// Options set local to the buffer, in the syntax of `set`.
+    pub settings: &'static [&'static str],
+    /// How to highlight files of this type, if at all.
+    pub syntax: Option<Syntax>,
+}
+
+/// The definitions of the known filetypes.
+pub const FILETYPES: &[FiletypeDef] = &[
+    FiletypeDef {
+        name: "rust",
+        extensions: &["rs"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &[],
+        settings: &["et", "sw=4", "tw=99"],
+        syntax: Some(Syntax {
+            keywords: &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match",
+                "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct",
+                "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+            line_comments: &["//"],
+            block_comment: Some(("/*", "*/")),
+            nested_comments: true,
+            strings: &['"'],
+            multiline_strings: true,
+            lifetimes: true,
+            attributes: &["#[", "#!["],
+            capitalized_types: true,
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "markdown",
+        extensions: &["md", "markdown"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &[],
+        settings: &[],
+        syntax: Some(Syntax {
+            strings: &['`'],
+            escape: None,
+            headings: &["#"],
+            code_fence: Some("```"),
+            symbols: false,
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "asm",
+        extensions: &["asm", "s"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &[],
+        settings: &[],
+        syntax: Some(Syntax {
+            keywords: &[
+                "section", "segment", "global", "extern", "db", "dw", "dd", "dq", "resb", "resw",
+                "resd", "resq", "equ", "times", "bits", "org", "align", "byte", "word", "dword",
+                "qword", "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "eax", "ebx",
+                "ecx", "edx", "esi", "edi", "ebp", "esp", "ax", "bx", "cx", "dx", "al", "bl", "cl",
+                "dl",
+            ],
+            line_comments: &[";"],
+            strings: &['"', '\'', '`'],
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "text",
+        extensions: &["txt"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &[],
+        settings: &[],
+        syntax: None,
+    },
+    FiletypeDef {
+        name: "list",
+        extensions: &["list"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &[],
+        settings: &[],
+        syntax: None,
+    },
+    FiletypeDef {
+        name: "toml",
+        extensions: &["toml"],
+        file_names: &["Cargo.lock"],
+        interpreters: &[],
+        first_line: &[],
+        settings: &[],
+        syntax: Some(Syntax {
+            keywords: &["true", "false"],
+            line_comments: &["#"],
+            multiline_strings: true,
+            headings: &["["],
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "make",
+        extensions: &["mk"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        interpreters: &["make"],
+        first_line: &[],
+        settings: &["noet", "sts=0"],
+        syntax: Some(Syntax {
+            keywords: &[
+                "ifeq", "ifneq", "ifdef", "ifndef", "else", "endif", "include", "define", "endef",
+                "export",
+            ],
+            strings: &[],
+            ..SHELL_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "sh",
+        extensions: &["sh", "bash"],
+        file_names: &[".bashrc", ".profile"],
+        interpreters: &["sh", "bash", "dash", "zsh"],
+        first_line: &[],
+        settings: &[],
+        syntax: Some(Syntax {
+            keywords: &[
+                "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case",
+                "esac", "in", "function", "return", "export", "local", "exit",
+            ],
+            ..SHELL_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "ion",
+        extensions: &["ion"],
+        file_names: &["initrc"],
+        interpreters: &["ion"],
+        first_line: &[],
+        settings: &[],
+        syntax: Some(Syntax {
+            keywords: &[
+                "if", "else", "end", "for", "while", "in", "fn", "let", "export", "match", "case",
+                "not", "and", "or", "test", "exit",
+            ],
+            ..SHELL_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "python",
+        extensions: &["py"],
+        file_names: &[],
+        interpreters: &["python", "python2", "python3"],
+        first_line: &[],
+        settings: &["et", "sw=4", "sts=4"],
+        syntax: Some(Syntax {
+            keywords: &[
+                "and", "as", "class", "def", "elif", "else", "except", "False", "for", "from",
+                "if", "import", "in", "is", "lambda", "None", "not", "or", "pass", "return",
+                "True", "try", "while", "with", "yield",
+            ],
+            line_comments: &["#"],
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "yaml",
+        extensions: &["yml", "yaml"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &["%YAML"],
+        settings: &["et", "sw=2", "sts=2"],
+        syntax: Some(Syntax {
+            keywords: &["true", "false", "null"],
+            line_comments: &["#"],
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "html",
+        extensions: &["html", "htm"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &["<!DOCTYPE html", "<!doctype html", "<html"],
+        settings: &[],
+        syntax: Some(Syntax {
+            block_comment: Some(("<!--", "-->")),
+            strings: &['"'],
+            escape: None,
+            multiline_strings: true,
+            ..PLAIN_SYNTAX
+        }),
+    },
+    FiletypeDef {
+        name: "xml",
+        extensions: &["xml", "svg"],
+        file_names: &[],
+        interpreters: &[],
+        first_line: &["<?xml"],
+        settings: &[],
+        syntax: Some(Syntax {
+            block_comment: Some(("<!--", "-->")),
+            strings: &['"'],
+            escape: None,
+            multiline_strings: true,
+            ..PLAIN_SYNTAX
+        }),
+    },
+];
+
+/// Find a filetype definition by its name.
+pub fn find_filetype(name: &str) -> Option<&'static FiletypeDef> {
+    FILETYPES.iter().find(|ft| ft.name == name)
+}
+
+/// Get the interpreter of a shebang line, e.g. `sh` for `#!/bin/sh` or `python3` for
+/// `#!/usr/bin/env python3`.
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        words.find(|w| !w.starts_with('-'))
+    } else {
+        Some(program)
+    }
+}
+
+/// Detect the filetype of a file from its path and its first line. The file name is checked
+/// first, then the extension, then the interpreter of a shebang line, and finally the start of
+/// the first line.
+pub fn detect_filetype(path: &str, first_line: &str) -> Option<&'static FiletypeDef> {
+    let path = Path::new(path);
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    FILETYPES
+        .iter()
+        .find(|ft| ft.file_names.contains(&file_name))
+        .or_else(|| {
+            FILETYPES
+                .iter()
+                .find(|ft| !extension.is_empty() && ft.extensions.contains(&extension))
+        })
+        .or_else(|| {
+            let interpreter = shebang_interpreter(first_line)?;
+            FILETYPES
+                .iter()
+                .find(|ft| ft.interpreters.contains(&interpreter))
+        })
+        .or_else(|| {
+            FILETYPES
+                .iter()
+                .find(|ft| ft.first_line.iter().any(|p| first_line.starts_with(p)))
+        })
+}
+
+impl Editor {
+    /// Get the definition of the filetype of the current buffer, if known.
+    pub fn filetype(&self) -> Option<&'static FiletypeDef> {
+        find_filetype(self.str_option("filetype"))
+    }
+
+    /// Detect the filetype of the current buffer, opened from `path`, and set it.
+    pub fn detect_filetype(&mut self, path: &str) {
+        let name = {
+            let first_line = self.buffers.current_buffer().get_line(0);
+            detect_filetype(path, first_line.map_or("", String::as_str)).map(|ft| ft.name)
+        };
+
+        if let Some(name) = name {
+            let _ = self.set_option(
+                "filetype",
+                OptionValue::Str(name.to_owned()),
+                SetTarget::Local,
+            );
+        }
+    }
+
+    /// Apply the settings of the filetype of the current buffer, and invoke the commands added
+    /// for it with `autocmd FileType`. This is done whenever the `filetype` option is set.
+    pub fn filetype_changed(&mut self) {
+        let name = self.str_option("filetype").to_owned();
+
+        if let Some(ft) = find_filetype(&name) {
+            for arg in ft.settings {
+                let _ = self.apply_option(arg, SetTarget::Local);
+            }
+        }
+
+        // Commands changing the filetype again would recurse
+        if self.source_depth >= MAX_SOURCE_DEPTH {
+            self.status_bar.msg = format!("FileType {}: Filetypes are set too deeply", name);
+            return;
+        }
+
+        let commands: Vec<String> = self
+            .filetype_hooks
+            .iter()
+            .filter(|&(pattern, _)| pattern == "*" || pattern.split(',').any(|p| p == name))
+            .map(|(_, command)| command.clone())
+            .collect();
+
+        self.source_depth += 1;
+        for command in commands {
+            let res = match PromptCommand::parse(&command) {
+                Some(cmd) => self.invoke(cmd),
+                None => Err(format!("Unknown command: {}", command)),
+            };
+            if let Err(msg) = res {
+                self.status_bar.msg = format!("FileType {}: {}", name, msg);
+            }
+        }
+        self.source_depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(path: &str, first_line: &str) -> Option<&'static str> {
+        detect_filetype(path, first_line).map(|ft| ft.name)
+    }
+
+    #[test]
+    fn shebang_interpreters() {
+        assert_eq!(shebang_interpreter("#!/bin/sh"), Some("sh"));
+        assert_eq!(shebang_interpreter("#! /bin/bash -e"), Some("bash"));
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env python3"),
+            Some("python3")
+        );
+        assert_eq!(shebang_interpreter("#!/usr/bin/env -S ion -x"), Some("ion"));
+        assert_eq!(shebang_interpreter("#!/usr/bin/env"), None);
+        assert_eq!(shebang_interpreter("# sh"), None);
+    }
+
+    #[test]
+    fn file_names_take_precedence_over_extensions() {
+        assert_eq!(detect("src/main.rs", ""), Some("rust"));
+        assert_eq!(detect("/a/b/Makefile", ""), Some("make"));
+        assert_eq!(detect("Cargo.lock", ""), Some("toml"));
+        assert_eq!(detect("script.sh", "#!/usr/bin/env python"), Some("sh"));
+        assert_eq!(detect("a.unknown", ""), None);
+        // A dotfile has no extension
+        assert_eq!(detect(".rs", ""), None);
+    }
+
+    #[test]
+    fn first_lines_identify_files_without_extension() {
+        assert_eq!(detect("script", "#!/usr/bin/env python"), Some("python"));
+        assert_eq!(detect("script", "#!/bin/dash"), Some("sh"));
+        assert_eq!(detect("page", "<!DOCTYPE html>"), Some("html"));
+        assert_eq!(detect("data", "<?xml version=\"1.0\"?>"), Some("xml"));
+        assert_eq!(detect("notes", "Hello"), None);
+    }
+}
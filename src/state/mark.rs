@@ -0,0 +1,355 @@
+use edit::buffer::TextBuffer;
+use io::file::FileStatus;
+use state::editor::Editor;
+
+/// The maximal number of entries in a position list.
+const MAX_POSITIONS: usize = 100;
+
+#[derive(Clone)]
+/// A global mark, i.e. a mark remembering the file it was set in.
+pub struct FileMark {
+    /// The path of the file.
+    pub path: String,
+    /// The position in the file.
+    pub pos: (usize, usize),
+}
+
+#[derive(Clone)]
+/// A list of positions, which can be walked backwards and forwards. This is used for the jump list
+/// and the change list.
+pub struct PositionList {
+    positions: Vec<(usize, usize)>,
+    index: usize,
+}
+
+impl Default for PositionList {
+    fn default() -> PositionList {
+        PositionList::new()
+    }
+}
+
+impl PositionList {
+    /// Create a new empty position list.
+    pub fn new() -> PositionList {
+        PositionList {
+            positions: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Add a position to the end of the list. Older entries on the same line are removed, and the
+    /// index is reset to the end of the list.
+    pub fn push(&mut self, pos: (usize, usize)) {
+        self.positions.retain(|&(_, y)| y != pos.1);
+        self.positions.push(pos);
+        if self.positions.len() > MAX_POSITIONS {
+            self.positions.remove(0);
+        }
+        self.index = self.positions.len();
+    }
+
+    /// Is the index past the newest entry?
+    pub fn at_end(&self) -> bool {
+        self.index >= self.positions.len()
+    }
+
+    /// Step back to the previous (older) entry.
+    pub fn older(&mut self) -> Option<(usize, usize)> {
+        if self.index == 0 || self.positions.is_empty() {
+            None
+        } else {
+            self.index = self.index.min(self.positions.len()) - 1;
+            Some(self.positions[self.index])
+        }
+    }
+
+    /// Step forward to the next (newer) entry.
+    pub fn newer(&mut self) -> Option<(usize, usize)> {
+        if self.index + 1 >= self.positions.len() {
+            None
+        } else {
+            self.index += 1;
+            Some(self.positions[self.index])
+        }
+    }
+
+    /// Get the entries, from the oldest to the newest.
+    pub fn positions(&self) -> &[(usize, usize)] {
+        &self.positions
+    }
+
+    /// Get the newest entry.
+    pub fn last(&self) -> Option<(usize, usize)> {
+        self.positions.last().cloned()
+    }
+
+    /// Shift the entries after `delta` lines were inserted (or removed, if negative) at line
+    /// `at`. Entries on removed lines move to the line following them.
+    pub fn shift_lines(&mut self, at: usize, delta: isize) {
+        for pos in &mut self.positions {
+            pos.1 = shift_line(pos.1, at, delta).unwrap_or(at);
+        }
+    }
+}
+
+/// Get the new number of line `y`, after `delta` lines were inserted (or removed, if negative)
+/// at line `at`, or `None` if the line was removed.
+fn shift_line(y: usize, at: usize, delta: isize) -> Option<usize> {
+    if y < at {
+        Some(y)
+    } else if delta >= 0 {
+        Some(y + delta as usize)
+    } else if y < at + delta.unsigned_abs() {
+        None
+    } else {
+        Some(y - delta.unsigned_abs())
+    }
+}
+
+impl Editor {
+    /// Set the mark `c` at the cursor. Lowercase marks are local to the buffer, uppercase marks
+    /// are global and remember the file.
+    pub fn set_mark(&mut self, c: char) {
+        let pos = self.pos();
+
+        if c.is_ascii_lowercase() {
+            self.buffers.current_buffer_info_mut().marks.insert(c, pos);
+            self.status_bar.msg = format!("Mark '{}' set", c);
+        } else if c.is_ascii_uppercase() {
+            // Transient buffers (like <Help>) are titled, but have no file to return to
+            let buffer = self.buffers.current_buffer_info();
+            let path = if buffer.is_transient {
+                None
+            } else {
+                buffer.title.clone()
+            };
+            if let Some(path) = path {
+                self.global_marks.insert(c, FileMark { path, pos });
+                self.status_bar.msg = format!("Mark '{}' set", c);
+            } else {
+                self.status_bar.msg = "Buffer has no file name".to_owned();
+            }
+        } else {
+            self.status_bar.msg = format!("Invalid mark: '{}'", c);
+        }
+    }
+
+    /// Get the position of the mark `c` in the current buffer. Global marks only resolve, if they
+    /// were set in the file of the current buffer.
+    pub fn mark_pos(&self, c: char) -> Option<(usize, usize)> {
+        let current_buffer = self.buffers.current_buffer_info();
+
+        if c == '\'' || c == '`' {
+            current_buffer.jumps.last()
+        } else if c.is_ascii_uppercase() {
+            self.global_marks.get(&c).and_then(|mark| {
+                if current_buffer.title.as_ref() == Some(&mark.path) {
+                    Some(mark.pos)
+                } else {
+                    None
+                }
+            })
+        } else {
+            current_buffer.marks.get(&c).cloned()
+        }
+    }
+
+    /// Jump to the mark `c`. If `linewise` is set, the cursor is placed at the first non-blank
+    /// character of the line. Global marks switch to (or open) the file the mark was set in.
+    pub fn goto_mark(&mut self, c: char, linewise: bool) {
+        if let Some(mark) = self.global_marks.get(&c).cloned() {
+            if self.buffers.current_buffer_info().title.as_ref() != Some(&mark.path) {
+                let existing = self
+                    .buffers
+                    .iter()
+                    .position(|b| b.title.as_ref() == Some(&mark.path));
+
+                match existing {
+                    Some(ix) => self.buffers.switch_to(ix),
+                    None => {
+                        if let FileStatus::NotFound = self.open(&mark.path) {
+                            self.status_bar.msg = format!("File {} could not be opened", mark.path);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+
+        match self.mark_pos(c) {
+            Some(pos) => {
+                let (x, y) = self.bound(pos, false);
+                let x = if linewise {
+                    self.buffers.current_buffer().get_indent(y).len()
+                } else {
+                    x
+                };
+
+                self.push_jump();
+                self.goto((x, y));
+            }
+            None => self.status_bar.msg = format!("Mark not set: '{}'", c),
+        }
+    }
+
+    /// Insert a line into the current buffer, moving the marks below it down.
+    pub fn insert_line(&mut self, n: usize, line: String) {
+        self.buffers.current_buffer_mut().insert_line(n, line);
+        self.shift_marks(n, 1);
+    }
+
+    /// Remove a line from the current buffer, deleting the marks on it and moving the marks
+    /// below it up.
+    pub fn remove_line(&mut self, n: usize) -> String {
+        let line = self.buffers.current_buffer_mut().remove_line(n);
+        self.shift_marks(n, -1);
+        line
+    }
+
+    /// Shift the marks, the jump list and the change list of the current buffer after `delta`
+    /// lines were inserted (or removed, if negative) at line `at`. Marks on removed lines are
+    /// deleted.
+    pub fn shift_marks(&mut self, at: usize, delta: isize) {
+        let buffer = self.buffers.current_buffer_info_mut();
+        buffer.marks = buffer
+            .marks
+            .drain()
+            .filter_map(|(c, (x, y))| shift_line(y, at, delta).map(|y| (c, (x, y))))
+            .collect();
+        buffer.jumps.shift_lines(at, delta);
+        buffer.changes.shift_lines(at, delta);
+
+        let title = buffer.title.clone();
+        self.global_marks.retain(|_, mark| {
+            if title.as_ref() != Some(&mark.path) {
+                return true;
+            }
+            match shift_line(mark.pos.1, at, delta) {
+                Some(y) => {
+                    mark.pos.1 = y;
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Record the cursor position in the jump list. This should be called before any "big" jump.
+    pub fn push_jump(&mut self) {
+        let pos = self.pos();
+        self.buffers.current_buffer_info_mut().jumps.push(pos);
+    }
+
+    /// Go to the n'th older position in the jump list.
+    pub fn jump_older(&mut self, n: usize) {
+        let pos = self.pos();
+        let mut target = None;
+
+        {
+            let jumps = &mut self.buffers.current_buffer_info_mut().jumps;
+            // Remember where we came from, so we can go forward again
+            if jumps.at_end() {
+                jumps.push(pos);
+                jumps.older();
+            }
+            for _ in 0..n {
+                match jumps.older() {
+                    Some(p) => target = Some(p),
+                    None => break,
+                }
+            }
+        }
+
+        match target {
+            Some(p) => {
+                let bounded = self.bound(p, false);
+                self.goto(bounded);
+            }
+            None => self.status_bar.msg = "At start of jump list".to_owned(),
+        }
+    }
+
+    /// Go to the n'th newer position in the jump list.
+    pub fn jump_newer(&mut self, n: usize) {
+        let mut target = None;
+
+        for _ in 0..n {
+            match self.buffers.current_buffer_info_mut().jumps.newer() {
+                Some(p) => target = Some(p),
+                None => break,
+            }
+        }
+
+        match target {
+            Some(p) => {
+                let bounded = self.bound(p, false);
+                self.goto(bounded);
+            }
+            None => self.status_bar.msg = "At end of jump list".to_owned(),
+        }
+    }
+
+    /// Record the cursor position in the change list. This should be called on every edit.
+    pub fn push_change(&mut self) {
+        let pos = self.pos();
+        self.buffers.current_buffer_info_mut().changes.push(pos);
+        // Edits reset the desired column, like horizontal motions
+        self.cursor_mut().want_col = None;
+    }
+
+    /// Go to the n'th older (`older` set) or newer position in the change list.
+    pub fn goto_change(&mut self, n: usize, older: bool) {
+        let mut target = None;
+
+        for _ in 0..n {
+            let changes = &mut self.buffers.current_buffer_info_mut().changes;
+            match if older {
+                changes.older()
+            } else {
+                changes.newer()
+            } {
+                Some(p) => target = Some(p),
+                None => break,
+            }
+        }
+
+        match target {
+            Some(p) => {
+                let bounded = self.bound(p, false);
+                self.goto(bounded);
+            }
+            None if older => self.status_bar.msg = "At start of change list".to_owned(),
+            None => self.status_bar.msg = "At end of change list".to_owned(),
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "orbital")))]
+mod tests {
+    use super::*;
+    use edit::buffer::SplitBuffer;
+
+    #[test]
+    fn marks_follow_inserted_and_removed_lines() {
+        let mut editor = Editor::new();
+        *editor.buffers.current_buffer_mut() = SplitBuffer::from_str("a\nb\nc\nd");
+        editor.goto((0, 2));
+        editor.set_mark('a');
+        editor.push_jump();
+        editor.goto((0, 1));
+        editor.set_mark('b');
+        editor.push_change();
+
+        editor.insert_line(0, String::new());
+        assert_eq!(editor.mark_pos('a'), Some((0, 3)));
+        assert_eq!(editor.mark_pos('b'), Some((0, 2)));
+
+        // Marks on removed lines are deleted, list entries move to the following line
+        editor.remove_line(2);
+        assert_eq!(editor.mark_pos('a'), Some((0, 2)));
+        assert_eq!(editor.mark_pos('b'), None);
+        let buffer = editor.buffers.current_buffer_info();
+        assert_eq!(buffer.jumps.last(), Some((0, 2)));
+        assert_eq!(buffer.changes.last(), Some((0, 2)));
+    }
+}
@@ -5,6 +5,13 @@
 pub mod cursor;
 /// The global editor state.
 pub mod editor;
+/// Filetypes, and their detection.
+pub mod filetype;
+/// Marks, the jump list and the change list.
+///
+/// Marks are named positions in a buffer (or, for global marks, in a file), which can be jumped to
+/// or used as motions.
+pub mod mark;
 /// Editor modes.
 pub mod mode;
 /// Options and configuration of the editor.
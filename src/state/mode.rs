@@ -22,6 +22,8 @@ impl Mode {
         use self::PrimitiveMode::*;
         match self {
             Command(Normal) => "Normal",
+            Command(Visual(VisualMode::Char)) => "Visual",
+            Command(Visual(VisualMode::Line)) => "Visual Line",
             Primitive(Insert(_)) => "Insert",
             Primitive(Prompt) => "Prompt",
         }
@@ -31,12 +33,23 @@ impl Mode {
 #[derive(Clone, PartialEq, Copy)]
 /// A command mode
 pub enum CommandMode {
-    // Visual(VisualOptions),
+    /// Visual mode. Motions extend a selection from the anchor of the cursor, and operators act on
+    /// the selection.
+    Visual(VisualMode),
     /// Normal mode. The default mode, which can be used for most common commands and switching to
     /// other modes.
     Normal,
 }
 
+#[derive(Clone, PartialEq, Copy)]
+/// The kind of a visual selection
+pub enum VisualMode {
+    /// Select characters (`v`).
+    Char,
+    /// Select whole lines (`V`).
+    Line,
+}
+
 #[derive(Clone, PartialEq, Copy)]
 /// A primitive mode
 pub enum PrimitiveMode {
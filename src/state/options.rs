@@ -1,86 +1,922 @@
-/// Editor options.
+use state::editor::Editor;
+
+use std::fmt;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// The type of an option.
+pub enum OptionKind {
+    /// A boolean, set with `set opt` and unset with `set noopt`.
+    Bool,
+    /// A non-negative integer.
+    Int,
+    /// A string.
+    Str,
+    /// A comma separated list of strings.
+    List,
+}
+
+impl OptionKind {
+    /// The name of the type.
+    pub fn name(self) -> &'static str {
+        match self {
+            OptionKind::Bool => "boolean",
+            OptionKind::Int => "number",
+            OptionKind::Str => "string",
+            OptionKind::List => "list",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// The value of an option.
+pub enum OptionValue {
+    /// A boolean.
+    Bool(bool),
+    /// A non-negative integer.
+    Int(usize),
+    /// A string.
+    Str(String),
+    /// A list of strings.
+    List(Vec<String>),
+}
+
+impl OptionValue {
+    /// Parse a value of the given type.
+    pub fn parse(kind: OptionKind, s: &str) -> Result<OptionValue, String> {
+        match kind {
+            OptionKind::Bool => match s {
+                "true" | "on" | "yes" | "1" => Ok(OptionValue::Bool(true)),
+                "false" | "off" | "no" | "0" => Ok(OptionValue::Bool(false)),
+                _ => Err(format!("Not a boolean: {}", s)),
+            },
+            OptionKind::Int => s
+                .parse()
+                .map(OptionValue::Int)
+                .map_err(|_| format!("Not a number: {}", s)),
+            OptionKind::Str => Ok(OptionValue::Str(s.to_owned())),
+            OptionKind::List => Ok(OptionValue::List(
+                s.split(',')
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.to_owned())
+                    .collect(),
+            )),
+        }
+    }
+
+    /// The type of the value.
+    pub fn kind(&self) -> OptionKind {
+        match *self {
+            OptionValue::Bool(_) => OptionKind::Bool,
+            OptionValue::Int(_) => OptionKind::Int,
+            OptionValue::Str(_) => OptionKind::Str,
+            OptionValue::List(_) => OptionKind::List,
+        }
+    }
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionValue::Bool(b) => write!(f, "{}", if b { "on" } else { "off" }),
+            OptionValue::Int(n) => write!(f, "{}", n),
+            OptionValue::Str(ref s) => write!(f, "{}", s),
+            OptionValue::List(ref l) => write!(f, "{}", l.join(",")),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// An error from getting or setting an option.
+pub enum OptionError {
+    /// No option with the given name exists.
+    Unknown(String),
+    /// The operation needs an option of another type, e.g. `set noopt` on a number.
+    WrongType {
+        /// The name of the option.
+        option: &'static str,
+        /// The type of the option.
+        kind: OptionKind,
+    },
+    /// The value is not valid for the option.
+    InvalidValue {
+        /// The name of the option.
+        option: &'static str,
+        /// Why the value is invalid.
+        reason: String,
+    },
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionError::Unknown(ref name) => write!(f, "Option does not exist: {}", name),
+            OptionError::WrongType { option, kind } => {
+                write!(f, "Option {} is a {}", option, kind.name())
+            }
+            OptionError::InvalidValue { option, ref reason } => {
+                write!(f, "Invalid value for {}: {}", option, reason)
+            }
+        }
+    }
+}
+
+/// A function checking the value of an option, returning why it's invalid, if it is.
+pub type Validator = fn(&OptionValue) -> Result<(), String>;
+
+#[derive(Copy, Clone, PartialEq)]
+/// The scope of an option.
+pub enum OptionScope {
+    /// The option has one value for the whole editor.
+    Global,
+    /// The option can have a value local to each buffer. Sodium has a single window, so window
+    /// local options (such as line numbers) are buffer-local as well.
+    Buffer,
+    /// The option only has values local to each buffer, and buffers without one see the
+    /// default (e.g. `filetype`, which must not carry over to other files).
+    Local,
+}
+
+/// The definition of an option.
+pub struct OptionDef {
+    /// The name of the option.
+    pub name: &'static str,
+    /// Alternative (usually shorter) names of the option.
+    pub aliases: &'static [&'static str],
+    /// The type of the option.
+    pub kind: OptionKind,
+    /// The scope of the option.
+    pub scope: OptionScope,
+    /// The default value, in the syntax of `set opt=value`.
+    pub default: &'static str,
+    /// Check a value before it's set.
+    pub validate: Option<Validator>,
+    /// A short description of the option.
+    pub description: &'static str,
+}
+
+fn positive(value: &OptionValue) -> Result<(), String> {
+    match *value {
+        OptionValue::Int(0) => Err("must be positive".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+/// The line ending formats, for `fileformat`.
+pub const FILE_FORMATS: &[&str] = &["unix", "dos", "mac"];
+
+/// The supported file encodings, for `fileencoding`.
+pub const FILE_ENCODINGS: &[&str] = &["utf-8", "utf-8-bom", "latin1", "utf-16be", "utf-16le"];
+
+fn file_format(value: &OptionValue) -> Result<(), String> {
+    match *value {
+        OptionValue::Str(ref s) if !FILE_FORMATS.contains(&s.as_str()) => {
+            Err(format!("must be one of {}", FILE_FORMATS.join(", ")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parse an item of `matchpairs`, e.g. `(:)`, into the opening and closing character.
+pub fn match_pair(item: &str) -> Option<(char, char)> {
+    let mut chars = item.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some(open), Some(':'), Some(close), None) if open != close => Some((open, close)),
+        _ => None,
+    }
+}
+
+fn match_pairs(value: &OptionValue) -> Result<(), String> {
+    match *value {
+        OptionValue::List(ref l) => match l.iter().find(|x| match_pair(x).is_none()) {
+            Some(x) => Err(format!("not a pair of two characters like (:): {}", x)),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// The modes of the sign column, for `signcolumn`.
+pub const SIGN_COLUMN_MODES: &[&str] = &["auto", "yes", "no"];
+
+fn sign_column(value: &OptionValue) -> Result<(), String> {
+    match *value {
+        OptionValue::Str(ref s) if !SIGN_COLUMN_MODES.contains(&s.as_str()) => {
+            Err(format!("must be one of {}", SIGN_COLUMN_MODES.join(", ")))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn file_encoding(value: &OptionValue) -> Result<(), String> {
+    match *value {
+        OptionValue::Str(ref s) if !FILE_ENCODINGS.contains(&s.as_str()) => {
+            Err(format!("must be one of {}", FILE_ENCODINGS.join(", ")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The definitions of every option.
+pub const OPTIONS: &[OptionDef] = &[
+    OptionDef {
+        name: "autoindent",
+        aliases: &["ai"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "on",
+        validate: None,
+        description: "Automatically indent new lines",
+    },
+    OptionDef {
+        name: "smartindent",
+        aliases: &["si"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "on",
+        validate: None,
+        description: "Indent new lines after blocks, and dedent closing braces",
+    },
+    OptionDef {
+        name: "debug",
+        aliases: &["debug_mode"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "on", // TODO: Let this be `on` only in debug compilation cfg
+        validate: None,
+        description: "Debug mode",
+    },
+    OptionDef {
+        name: "highlight",
+        aliases: &["hl"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "on",
+        validate: None,
+        description: "Highlight the text",
+    },
+    OptionDef {
+        name: "hlsearch",
+        aliases: &["hls"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "off",
+        validate: None,
+        description: "Highlight the matches of the last search pattern",
+    },
+    OptionDef {
+        name: "line_marker",
+        aliases: &["linemarker", "linemark", "lm"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "on",
+        validate: None,
+        description: "Dim the background of the current line",
+    },
+    OptionDef {
+        name: "wrap",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "on",
+        validate: None,
+        description: "Wrap long lines, instead of scrolling horizontally",
+    },
+    OptionDef {
+        name: "linebreak",
+        aliases: &["lbr"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "off",
+        validate: None,
+        description: "Wrap long lines at blanks",
+    },
+    OptionDef {
+        name: "displaylines",
+        aliases: &["dl"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "off",
+        validate: None,
+        description: "Move j and k by screen rows of wrapped lines",
+    },
+    OptionDef {
+        name: "showbreak",
+        aliases: &["sbr"],
+        kind: OptionKind::Str,
+        scope: OptionScope::Global,
+        default: "",
+        validate: None,
+        description: "The marker shown at the start of wrapped rows",
+    },
+    OptionDef {
+        name: "sidescroll",
+        aliases: &["ss"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Global,
+        default: "0",
+        validate: None,
+        description: "Columns to scroll horizontally at least, or 0 to center the cursor",
+    },
+    OptionDef {
+        name: "sidescrolloff",
+        aliases: &["siso"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Global,
+        default: "0",
+        validate: None,
+        description: "Columns kept left and right of the cursor when scrolling horizontally",
+    },
+    OptionDef {
+        name: "scrolloff",
+        aliases: &["so"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Global,
+        default: "1",
+        validate: None,
+        description: "Lines kept above and below the cursor when scrolling",
+    },
+    OptionDef {
+        name: "matchpairs",
+        aliases: &["mps"],
+        kind: OptionKind::List,
+        scope: OptionScope::Buffer,
+        default: "(:),[:],{:}",
+        validate: Some(match_pairs),
+        description: "The pairs of characters % jumps between",
+    },
+    OptionDef {
+        name: "readonly",
+        aliases: &["ro"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "off",
+        validate: None,
+        description: "Don't allow writing the buffer",
+    },
+    OptionDef {
+        name: "line_numbers",
+        aliases: &["ln", "number", "nu"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "off",
+        validate: None,
+        description: "Show line numbers",
+    },
+    OptionDef {
+        name: "relativenumber",
+        aliases: &["rnu"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "off",
+        validate: None,
+        description: "Show line numbers relative to the cursor",
+    },
+    OptionDef {
+        name: "signcolumn",
+        aliases: &["scl"],
+        kind: OptionKind::Str,
+        scope: OptionScope::Global,
+        default: "no",
+        validate: Some(sign_column),
+        description: "Show the sign column of marks and changes: auto, yes or no",
+    },
+    OptionDef {
+        name: "textwidth",
+        aliases: &["tw"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Buffer,
+        default: "79",
+        validate: Some(positive),
+        description: "The width to format text to",
+    },
+    OptionDef {
+        name: "tabstop",
+        aliases: &["ts"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Buffer,
+        default: "4",
+        validate: Some(positive),
+        description: "The width of a tab",
+    },
+    OptionDef {
+        name: "shiftwidth",
+        aliases: &["sw"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Buffer,
+        default: "4",
+        validate: Some(positive),
+        description: "The width of a level of indentation",
+    },
+    OptionDef {
+        name: "softtabstop",
+        aliases: &["sts"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Buffer,
+        default: "0",
+        validate: None,
+        description: "The width of a tab (and backspace) in insert mode, or 0 to use tabstop",
+    },
+    OptionDef {
+        name: "expandtab",
+        aliases: &["et"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "on",
+        validate: None,
+        description: "Insert spaces instead of tabs",
+    },
+    OptionDef {
+        name: "detectindent",
+        aliases: &["di"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "on",
+        validate: None,
+        description: "Set the tab options of opened files to match their indentation",
+    },
+    OptionDef {
+        name: "editorconfig",
+        aliases: &["ec"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "on",
+        validate: None,
+        description: "Apply the .editorconfig files of opened files",
+    },
+    OptionDef {
+        name: "filetype",
+        aliases: &["ft"],
+        kind: OptionKind::Str,
+        scope: OptionScope::Local,
+        default: "",
+        validate: None,
+        description: "The type of the file, e.g. rust or markdown",
+    },
+    OptionDef {
+        name: "fileformat",
+        aliases: &["ff"],
+        kind: OptionKind::Str,
+        scope: OptionScope::Buffer,
+        default: "unix",
+        validate: Some(file_format),
+        description: "The line endings to write: unix, dos or mac",
+    },
+    OptionDef {
+        name: "fileencoding",
+        aliases: &["fenc"],
+        kind: OptionKind::Str,
+        scope: OptionScope::Buffer,
+        default: "utf-8",
+        validate: Some(file_encoding),
+        description: "The encoding to write: utf-8, utf-8-bom, latin1, utf-16be or utf-16le",
+    },
+    OptionDef {
+        name: "endofline",
+        aliases: &["eol"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "on",
+        validate: None,
+        description: "End the last line with a line ending when writing",
+    },
+    OptionDef {
+        name: "trimwhitespace",
+        aliases: &["tws"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Buffer,
+        default: "off",
+        validate: None,
+        description: "Remove trailing whitespace when writing",
+    },
+    OptionDef {
+        name: "modeline",
+        aliases: &["ml"],
+        kind: OptionKind::Bool,
+        scope: OptionScope::Global,
+        default: "on",
+        validate: None,
+        description: "Apply the modelines of opened files",
+    },
+    OptionDef {
+        name: "modelines",
+        aliases: &["mls"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Global,
+        default: "5",
+        validate: None,
+        description: "The number of lines at the start and end of a file checked for modelines",
+    },
+    OptionDef {
+        name: "timeoutlen",
+        aliases: &["tm"],
+        kind: OptionKind::Int,
+        scope: OptionScope::Global,
+        default: "1000",
+        validate: None,
+        description: "Milliseconds to wait for the rest of a mapping",
+    },
+];
+
+/// Find an option definition by its name or an alias. Returns the index of the option as well.
+pub fn find_option(name: &str) -> Result<(usize, &'static OptionDef), OptionError> {
+    OPTIONS
+        .iter()
+        .enumerate()
+        .find(|&(_, def)| def.name == name || def.aliases.contains(&name))
+        .ok_or_else(|| OptionError::Unknown(name.to_owned()))
+}
+
+/// Global option values.
 pub struct Options {
-    /// Autoindent on line breaks?
-    pub autoindent: bool,
-    /// Debug mode.
-    pub debug: bool,
-    /// Highlight.
-    pub highlight: bool,
-    /// Line marker (dimmed background of the current line).
-    pub line_marker: bool,
-    /// enables read-only mode
-    pub readonly: bool,
-    /// Enable linenumbers
-    pub line_numbers: bool,
+    /// The values of the options, in the order of `OPTIONS`.
+    values: Vec<OptionValue>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::new()
+    }
 }
 
 impl Options {
     /// Create new default options
     pub fn new() -> Self {
         Options {
-            autoindent: true,
-            debug: true, // TODO: Let this be `true` only in debug compilation cfg
-            highlight: true,
-            line_marker: true,
-            readonly: false,
-            line_numbers: false,
+            values: OPTIONS.iter().map(default_value).collect(),
+        }
+    }
+
+    /// Get the global value of a given option
+    pub fn get(&self, name: &str) -> Result<&OptionValue, OptionError> {
+        let (ix, _) = find_option(name)?;
+        Ok(&self.values[ix])
+    }
+
+    /// Set the global value of a given option, after validating it.
+    pub fn set_value(&mut self, name: &str, value: OptionValue) -> Result<(), OptionError> {
+        let (ix, def) = find_option(name)?;
+        check_value(def, &value)?;
+        self.values[ix] = value;
+        Ok(())
+    }
+
+    /// Set a given option (mark it as active)
+    pub fn set(&mut self, name: &str) -> Result<(), OptionError> {
+        self.set_value(name, OptionValue::Bool(true))
+    }
+}
+
+/// Buffer-local option values, overriding the global values.
+pub struct LocalOptions {
+    /// The local values of the options, in the order of `OPTIONS`.
+    values: Vec<Option<OptionValue>>,
+}
+
+impl Default for LocalOptions {
+    fn default() -> LocalOptions {
+        LocalOptions::new()
+    }
+}
+
+impl LocalOptions {
+    /// Create new local options, without any overrides.
+    pub fn new() -> LocalOptions {
+        LocalOptions {
+            values: vec![None; OPTIONS.len()],
         }
     }
+}
 
-    /// Get the given option as a mutable reference
-    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
-        match name {
-            "autoindent" | "ai" => Some(&mut self.autoindent),
-            "debug" | "debug_mode" => Some(&mut self.debug),
-            "highlight" | "hl" => Some(&mut self.highlight),
-            "line_marker" | "linemarker" | "linemark" | "lm" => Some(&mut self.line_marker),
-            "readonly" | "ro" => Some(&mut self.readonly),
-            "line_numbers" | "ln" => Some(&mut self.line_numbers),
-            _ => None,
+#[derive(Copy, Clone, PartialEq)]
+/// The values, which a `set` command changes.
+pub enum SetTarget {
+    /// Both the global value and the value local to the current buffer (`set`).
+    Both,
+    /// Only the value local to the current buffer (`setlocal`).
+    Local,
+    /// Only the global value (`setglobal`).
+    Global,
+}
+
+impl Editor {
+    /// Get the value of a given option in the current buffer, i.e. the buffer-local value, if
+    /// any, otherwise the global value.
+    pub fn option(&self, name: &str) -> Result<&OptionValue, OptionError> {
+        let (ix, _) = find_option(name)?;
+        Ok(self.option_value(ix, SetTarget::Both))
+    }
+
+    /// Get a boolean option. Panics if the option is not a defined boolean.
+    pub fn bool_option(&self, name: &str) -> bool {
+        match self.option(name) {
+            Ok(&OptionValue::Bool(b)) => b,
+            _ => panic!("Option {} is not a boolean", name),
         }
     }
 
-    /// Get a given option
-    pub fn get(&self, name: &str) -> Option<bool> {
-        match name {
-            "autoindent" | "ai" => Some(self.autoindent),
-            "debug" | "debug_mode" => Some(self.debug),
-            "highlight" | "hl" => Some(self.highlight),
-            "line_marker" | "linemarker" | "linemark" | "lm" => Some(self.line_marker),
-            "readonly" | "ro" => Some(self.readonly),
-            "line_numbers" | "ln" => Some(self.line_numbers),
-            _ => None,
+    /// Get a number option. Panics if the option is not a defined number.
+    pub fn int_option(&self, name: &str) -> usize {
+        match self.option(name) {
+            Ok(&OptionValue::Int(n)) => n,
+            _ => panic!("Option {} is not a number", name),
         }
     }
 
-    /// Set a given option (mark it as active)
-    pub fn set(&mut self, name: &str) -> Result<(), ()> {
-        match self.get_mut(name) {
-            Some(x) => {
-                *x = true;
-                Ok(())
-            }
-            None => Err(()),
+    /// Get a string option. Panics if the option is not a defined string.
+    pub fn str_option(&self, name: &str) -> &str {
+        match self.option(name) {
+            Ok(OptionValue::Str(s)) => s,
+            _ => panic!("Option {} is not a string", name),
         }
     }
-    /// Unset a given option (mark it as inactive)
-    pub fn unset(&mut self, name: &str) -> Result<(), ()> {
-        match self.get_mut(name) {
-            Some(x) => {
-                *x = false;
-                Ok(())
-            }
-            None => Err(()),
+
+    /// Get a list option. Panics if the option is not a defined list.
+    pub fn list_option(&self, name: &str) -> &[String] {
+        match self.option(name) {
+            Ok(OptionValue::List(l)) => l,
+            _ => panic!("Option {} is not a list", name),
+        }
+    }
+
+    /// The value of the option with index `ix`, as seen by `target`.
+    fn option_value(&self, ix: usize, target: SetTarget) -> &OptionValue {
+        let local = &self.buffers.current_buffer_info().options.values[ix];
+        match (target, local) {
+            (SetTarget::Global, _) | (_, None) => &self.options.values[ix],
+            (_, Some(value)) => value,
+        }
+    }
+
+    /// Set a given option to a value, after validating it. Global options are always set
+    /// globally, and local options always locally.
+    pub fn set_option(
+        &mut self,
+        name: &str,
+        value: OptionValue,
+        target: SetTarget,
+    ) -> Result<(), OptionError> {
+        let (ix, def) = find_option(name)?;
+        check_value(def, &value)?;
+
+        let target = scope_target(def, target);
+        let changed = match target {
+            SetTarget::Global => self.options.values[ix] != value,
+            _ => self.buffers.current_buffer_info().options.values[ix].as_ref() != Some(&value),
+        };
+        if target != SetTarget::Global {
+            self.buffers.current_buffer_info_mut().options.values[ix] = Some(value.clone());
+        }
+        if target != SetTarget::Local {
+            self.options.values[ix] = value;
+        }
+
+        if def.name == "filetype" && changed {
+            self.filetype_changed();
+        }
+        Ok(())
+    }
+
+    /// Toggle a given boolean option.
+    pub fn toggle_option(&mut self, name: &str, target: SetTarget) -> Result<(), OptionError> {
+        let (ix, def) = find_option(name)?;
+        match *self.option_value(ix, target) {
+            OptionValue::Bool(b) => self.set_option(name, OptionValue::Bool(!b), target),
+            _ => Err(OptionError::WrongType {
+                option: def.name,
+                kind: def.kind,
+            }),
+        }
+    }
+
+    /// Reset a given option to its default value. Resetting the local value removes it, such
+    /// that the global value is used.
+    pub fn reset_option(&mut self, name: &str, target: SetTarget) -> Result<(), OptionError> {
+        let (ix, def) = find_option(name)?;
+        let target = scope_target(def, target);
+        if target != SetTarget::Global {
+            self.buffers.current_buffer_info_mut().options.values[ix] = None;
         }
+        if target != SetTarget::Local {
+            self.options.values[ix] = default_value(def);
+        }
+        Ok(())
     }
-    /// Toggle a given option
-    pub fn toggle(&mut self, name: &str) -> Result<(), ()> {
-        match self.get_mut(name) {
-            Some(x) => {
-                *x = !*x;
-                Ok(())
+
+    /// Apply an argument of the `set` command to the options:
+    ///
+    /// - `opt`: Set a boolean option, or show the value of another option
+    /// - `noopt`: Unset a boolean option
+    /// - `invopt` or `opt!`: Toggle a boolean option
+    /// - `opt?`: Show the value of the option
+    /// - `opt&`: Reset the option to its default
+    /// - `opt=value`: Set the option to `value`
+    /// - `opt+=value`, `opt-=value`: Add to or subtract from a number, or add or remove an item
+    ///   of a list
+    ///
+    /// Returns a message describing the result.
+    pub fn apply_option(&mut self, arg: &str, target: SetTarget) -> Result<String, OptionError> {
+        let (name, op, value) = parse_set_arg(arg);
+
+        match op {
+            "=" => {
+                let (_, def) = find_option(name)?;
+                let value = OptionValue::parse(def.kind, value).map_err(|reason| {
+                    OptionError::InvalidValue {
+                        option: def.name,
+                        reason,
+                    }
+                })?;
+                self.set_option(name, value, target)?;
+                self.show_option(name, target)
+            }
+            "+=" | "-=" => {
+                let (ix, def) = find_option(name)?;
+                let new = match self.option_value(ix, target).clone() {
+                    OptionValue::Int(n) => {
+                        let m: usize = value.parse().map_err(|_| OptionError::InvalidValue {
+                            option: def.name,
+                            reason: format!("Not a number: {}", value),
+                        })?;
+                        OptionValue::Int(if op == "+=" {
+                            n + m
+                        } else {
+                            n.saturating_sub(m)
+                        })
+                    }
+                    OptionValue::List(mut l) => {
+                        let items = value.split(',').filter(|x| !x.is_empty());
+                        if op == "+=" {
+                            l.extend(items.map(|x| x.to_owned()));
+                        } else {
+                            let items: Vec<&str> = items.collect();
+                            l.retain(|x| !items.contains(&x.as_str()));
+                        }
+                        OptionValue::List(l)
+                    }
+                    _ => {
+                        return Err(OptionError::WrongType {
+                            option: def.name,
+                            kind: def.kind,
+                        })
+                    }
+                };
+                self.set_option(name, new, target)?;
+                self.show_option(name, target)
+            }
+            "?" => self.show_option(name, target),
+            "&" => {
+                self.reset_option(name, target)?;
+                self.show_option(name, target)
             }
-            None => Err(()),
+            "!" => {
+                self.toggle_option(name, target)?;
+                self.show_option(name, target)
+            }
+            _ => match find_option(name) {
+                Ok((_, def)) if def.kind == OptionKind::Bool => {
+                    self.set_option(name, OptionValue::Bool(true), target)?;
+                    Ok(format!("Option set: {}", def.name))
+                }
+                Ok(_) => self.show_option(name, target),
+                Err(e) => {
+                    if name.starts_with("no") && find_option(&name[2..]).is_ok() {
+                        self.set_option(&name[2..], OptionValue::Bool(false), target)?;
+                        Ok(format!("Option unset: {}", &name[2..]))
+                    } else if name.starts_with("inv") && find_option(&name[3..]).is_ok() {
+                        self.toggle_option(&name[3..], target)?;
+                        self.show_option(&name[3..], target)
+                    } else {
+                        Err(e)
+                    }
+                }
+            },
+        }
+    }
+
+    /// Describe the value of an option, e.g. `textwidth=79` or `noautoindent`.
+    pub fn show_option(&self, name: &str, target: SetTarget) -> Result<String, OptionError> {
+        let (ix, def) = find_option(name)?;
+        Ok(match *self.option_value(ix, target) {
+            OptionValue::Bool(true) => def.name.to_owned(),
+            OptionValue::Bool(false) => format!("no{}", def.name),
+            ref value => format!("{}={}", def.name, value),
+        })
+    }
+
+    /// Describe the values of every option. For `SetTarget::Local` only the options with a
+    /// value local to the current buffer are described.
+    pub fn describe_options(&self, target: SetTarget) -> String {
+        let local = &self.buffers.current_buffer_info().options.values;
+        OPTIONS
+            .iter()
+            .enumerate()
+            .filter(|&(ix, _)| target != SetTarget::Local || local[ix].is_some())
+            .filter_map(|(_, def)| self.show_option(def.name, target).ok())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Get the definition of the option an argument of `set` refers to, including `noopt` and
+/// `invopt`.
+pub fn set_arg_option(arg: &str) -> Result<&'static OptionDef, OptionError> {
+    let (name, _, _) = parse_set_arg(arg);
+    find_option(name)
+        .or_else(
+            |e| match name.strip_prefix("no").or_else(|| name.strip_prefix("inv")) {
+                Some(name) => find_option(name).map_err(|_| e),
+                None => Err(e),
+            },
+        )
+        .map(|(_, def)| def)
+}
+
+/// Split an argument of `set` into the option name, the operator and the value.
+fn parse_set_arg(arg: &str) -> (&str, &str, &str) {
+    // The value may contain any character, so only the first `=` splits the argument
+    if let Some(i) = arg.find('=') {
+        let value = &arg[i + 1..];
+        return match &arg[..i] {
+            name if name.ends_with('+') => (&name[..name.len() - 1], "+=", value),
+            name if name.ends_with('-') => (&name[..name.len() - 1], "-=", value),
+            name => (name, "=", value),
+        };
+    }
+    for op in &["?", "&", "!"] {
+        if arg.ends_with(op) {
+            return (&arg[..arg.len() - 1], op, "");
         }
     }
+    (arg, "", "")
+}
+
+/// The values of an option, which a `set` command with a given target actually changes.
+fn scope_target(def: &OptionDef, target: SetTarget) -> SetTarget {
+    match def.scope {
+        OptionScope::Global => SetTarget::Global,
+        OptionScope::Local => SetTarget::Local,
+        OptionScope::Buffer => target,
+    }
+}
+
+fn default_value(def: &OptionDef) -> OptionValue {
+    OptionValue::parse(def.kind, def.default).expect("Invalid default value in option definition")
+}
+
+/// Check that a value has the type of the option and passes its validation.
+fn check_value(def: &OptionDef, value: &OptionValue) -> Result<(), OptionError> {
+    if value.kind() != def.kind {
+        return Err(OptionError::WrongType {
+            option: def.name,
+            kind: def.kind,
+        });
+    }
+    if let Some(validate) = def.validate {
+        validate(value).map_err(|reason| OptionError::InvalidValue {
+            option: def.name,
+            reason,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_arg_splits_at_the_first_equals_sign() {
+        assert_eq!(parse_set_arg("tw=72"), ("tw", "=", "72"));
+        assert_eq!(parse_set_arg("tw+=8"), ("tw", "+=", "8"));
+        assert_eq!(parse_set_arg("mps-=<:>"), ("mps", "-=", "<:>"));
+        assert_eq!(parse_set_arg("showbreak=a+=b"), ("showbreak", "=", "a+=b"));
+        assert_eq!(parse_set_arg("showbreak=-="), ("showbreak", "=", "-="));
+        assert_eq!(parse_set_arg("tw?"), ("tw", "?", ""));
+        assert_eq!(parse_set_arg("ai!"), ("ai", "!", ""));
+        assert_eq!(parse_set_arg("noai"), ("noai", "", ""));
+    }
+
+    #[test]
+    fn list_values_are_split_at_commas() {
+        let value = OptionValue::parse(OptionKind::List, "(:),[:],,{:}").unwrap();
+        assert_eq!(
+            value,
+            OptionValue::List(vec!["(:)".into(), "[:]".into(), "{:}".into()])
+        );
+        assert_eq!(value.to_string(), "(:),[:],{:}");
+    }
+
+    #[test]
+    fn match_pairs_are_validated() {
+        assert_eq!(match_pair("<:>"), Some(('<', '>')));
+        assert_eq!(match_pair("«:»"), Some(('«', '»')));
+        assert_eq!(match_pair("<>"), None);
+        assert_eq!(match_pair("<:>>"), None);
+        assert_eq!(match_pair("|:|"), None);
+
+        let (_, def) = find_option("mps").unwrap();
+        assert!(check_value(def, &default_value(def)).is_ok());
+        let bad = OptionValue::List(vec!["(:)".into(), "ab".into()]);
+        assert!(check_value(def, &bad).is_err());
+    }
 }